@@ -0,0 +1,121 @@
+use openssl::{
+    asn1::Asn1Time,
+    bn::{BigNum, MsbOption},
+    ec::{EcGroup, EcKey},
+    error::ErrorStack,
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{PKey, Private},
+    x509::{
+        extension::{
+            BasicConstraints, ExtendedKeyUsage, KeyUsage, SubjectAlternativeName,
+            SubjectKeyIdentifier,
+        },
+        X509NameBuilder, X509,
+    },
+};
+
+use crate::error::CustomError;
+
+const VALID_DAYS: u32 = 825;
+
+/// Returns the DNS names in the subject alternative names of a PEM certificate.
+pub fn get_certificate_domains(pem: &[u8]) -> Option<Vec<String>> {
+    let certificate = X509::from_pem(pem).ok()?;
+    if certificate.not_after() < Asn1Time::days_from_now(1).ok()? {
+        return None;
+    }
+    Some(
+        certificate
+            .subject_alt_names()?
+            .iter()
+            .filter_map(|name| name.dnsname().map(|x| x.to_string()))
+            .collect(),
+    )
+}
+
+fn build_certificate(domains: &[String]) -> Result<(X509, PKey<Private>), ErrorStack> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    let key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_nid(Nid::ORGANIZATIONNAME, "ee-nginx self-signed")?;
+    name.append_entry_by_nid(
+        Nid::COMMONNAME,
+        domains.first().map(|x| x.as_str()).unwrap_or("localhost"),
+    )?;
+    let name = name.build();
+
+    let mut serial = BigNum::new()?;
+    serial.rand(128, MsbOption::MAYBE_ZERO, false)?;
+    let serial = serial.to_asn1_integer()?;
+    let not_before = Asn1Time::days_from_now(0)?;
+    let not_after = Asn1Time::days_from_now(VALID_DAYS)?;
+
+    let mut builder = X509::builder()?;
+    builder.set_version(2)?;
+    builder.set_serial_number(&serial)?;
+    builder.set_subject_name(&name)?;
+    builder.set_issuer_name(&name)?;
+    builder.set_pubkey(&key)?;
+    builder.set_not_before(&not_before)?;
+    builder.set_not_after(&not_after)?;
+
+    builder.append_extension(BasicConstraints::new().build()?)?;
+    builder.append_extension(
+        KeyUsage::new()
+            .critical()
+            .digital_signature()
+            .key_agreement()
+            .build()?,
+    )?;
+    builder.append_extension(ExtendedKeyUsage::new().server_auth().build()?)?;
+    let subject_key_identifier =
+        SubjectKeyIdentifier::new().build(&builder.x509v3_context(None, None))?;
+    builder.append_extension(subject_key_identifier)?;
+    let mut san = SubjectAlternativeName::new();
+    for domain in domains {
+        san.dns(domain);
+    }
+    let san = san.build(&builder.x509v3_context(None, None))?;
+    builder.append_extension(san)?;
+
+    builder.sign(&key, MessageDigest::sha256())?;
+    Ok((builder.build(), key))
+}
+
+/// Creates a self-signed certificate for `domains`, returns (certificate, key) in PEM format.
+pub fn create_self_signed_certificate(domains: &[String]) -> Result<(String, String), CustomError> {
+    let to_pem = || -> Result<(Vec<u8>, Vec<u8>), ErrorStack> {
+        let (certificate, key) = build_certificate(domains)?;
+        Ok((certificate.to_pem()?, key.private_key_to_pem_pkcs8()?))
+    };
+    let (certificate, key) = to_pem().map_err(|e| {
+        CustomError::new(format!("failed to create self-signed certificate, {:?}", e))
+    })?;
+    Ok((
+        String::from_utf8(certificate).unwrap(),
+        String::from_utf8(key).unwrap(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_self_signed_certificate() {
+        let domains = vec!["foo.localhost".to_string(), "bar.localhost".to_string()];
+        let (certificate, key) =
+            create_self_signed_certificate(&domains).expect("failed to create certificate");
+        assert_eq!(
+            get_certificate_domains(certificate.as_bytes()),
+            Some(domains)
+        );
+
+        let x509 = X509::from_pem(certificate.as_bytes()).unwrap();
+        let key = PKey::private_key_from_pem(key.as_bytes()).unwrap();
+        assert!(x509.verify(&key).unwrap());
+        assert!(x509.public_key().unwrap().public_eq(&key));
+    }
+}
@@ -1,20 +1,146 @@
-#[derive(Debug)]
-pub struct CustomError {
-    message: String,
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub width: usize,
+    pub snippet: String,
 }
 
-impl CustomError {
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub help: Option<String>,
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
     pub fn new(message: impl Into<String>) -> Self {
-        CustomError {
+        Diagnostic {
             message: message.into(),
+            help: None,
+            span: None,
         }
     }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "error: {}", self.message)?;
+        let gutter = match &self.span {
+            Some(span) => {
+                let gutter = " ".repeat(span.line.to_string().len());
+                writeln!(f, "{}--> {}:{}", gutter, span.line, span.column)?;
+                writeln!(f, "{} |", gutter)?;
+                writeln!(f, "{} | {}", span.line, span.snippet)?;
+                writeln!(
+                    f,
+                    "{} | {}{}",
+                    gutter,
+                    " ".repeat(span.column - 1),
+                    "^".repeat(span.width)
+                )?;
+                gutter
+            }
+            None => "".to_string(),
+        };
+        if let Some(help) = &self.help {
+            writeln!(f, "{} = help: {}", gutter, help)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum CustomError {
+    Parse(Vec<Diagnostic>),
+    Message(String),
+}
+
+impl CustomError {
+    pub fn new(message: impl Into<String>) -> Self {
+        CustomError::Message(message.into())
+    }
 }
 
 impl std::fmt::Display for CustomError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error: {}", self.message)
+        match self {
+            CustomError::Parse(diagnostics) => {
+                for diagnostic in diagnostics {
+                    writeln!(f, "{}", diagnostic)?;
+                }
+                write!(
+                    f,
+                    "error: could not parse configuration due to {} previous error{}",
+                    diagnostics.len(),
+                    if diagnostics.len() == 1 { "" } else { "s" }
+                )
+            }
+            CustomError::Message(message) => write!(f, "Error: {}", message),
+        }
     }
 }
 
 impl std::error::Error for CustomError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_diagnostic() {
+        let diagnostic = Diagnostic::new("unknown target option 'versionned'")
+            .with_help("did you mean 'versioned'?")
+            .with_span(Span {
+                line: 3,
+                column: 24,
+                width: 10,
+                snippet: "/static > /mnt/static/?versionned".to_string(),
+            });
+        assert_eq!(
+            diagnostic.to_string(),
+            r#"error: unknown target option 'versionned'
+ --> 3:24
+  |
+3 | /static > /mnt/static/?versionned
+  |                        ^^^^^^^^^^
+  = help: did you mean 'versioned'?
+"#
+        );
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let error = CustomError::Parse(vec![
+            Diagnostic::new("route must include exactly one '>'").with_span(Span {
+                line: 12,
+                column: 1,
+                width: 7,
+                snippet: "/static".to_string(),
+            }),
+            Diagnostic::new("no domain found"),
+        ]);
+        assert_eq!(
+            error.to_string(),
+            r#"error: route must include exactly one '>'
+  --> 12:1
+   |
+12 | /static
+   | ^^^^^^^
+
+error: no domain found
+
+error: could not parse configuration due to 2 previous errors"#
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use base64::{engine::general_purpose, Engine};
+use bcrypt::Version;
+use openssl::{
+    hash::{Hasher, MessageDigest},
+    rand::rand_bytes,
+};
+
+use crate::{error::CustomError, HashAlgorithm};
+
+/// Same as the default of `htpasswd -B`, nginx verifies the hash on every request.
+const BCRYPT_COST: u32 = 5;
+
+const ITOA64: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+fn md5(parts: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = Hasher::new(MessageDigest::md5()).unwrap();
+    for part in parts {
+        hasher.update(part).unwrap();
+    }
+    hasher.finish().unwrap().to_vec()
+}
+
+fn hash_sha(password: &str) -> String {
+    let mut hasher = Hasher::new(MessageDigest::sha1()).unwrap();
+    hasher.update(password.as_bytes()).unwrap();
+    format!(
+        "{{SHA}}{}",
+        general_purpose::STANDARD.encode(hasher.finish().unwrap())
+    )
+}
+
+fn hash_bcrypt(password: &str, salt: [u8; 16]) -> Result<String, CustomError> {
+    bcrypt::hash_with_salt(password, BCRYPT_COST, salt)
+        .map(|x| x.format_for_version(Version::TwoY))
+        .map_err(|e| CustomError::new(format!("bcrypt failed, Error:{:?}", e)))
+}
+
+/// The MD5 based crypt of Apache, `salt` is up to 8 characters of `ITOA64`.
+fn hash_apr1(password: &str, salt: &str) -> String {
+    const MAGIC: &[u8] = b"$apr1$";
+    let password = password.as_bytes();
+    let salt = &salt.as_bytes()[..salt.len().min(8)];
+
+    let alternate = md5(&[password, salt, password]);
+    let mut parts = vec![password, MAGIC, salt];
+    for chunk in password.chunks(16) {
+        parts.push(&alternate[..chunk.len()]);
+    }
+    let mut length = password.len();
+    while length > 0 {
+        parts.push(if length & 1 == 1 {
+            &[0]
+        } else {
+            &password[..1]
+        });
+        length >>= 1;
+    }
+    let mut digest = md5(&parts);
+
+    for i in 0..1000 {
+        let mut parts: Vec<&[u8]> = vec![];
+        parts.push(if i % 2 == 1 { password } else { &digest });
+        if i % 3 != 0 {
+            parts.push(salt);
+        }
+        if i % 7 != 0 {
+            parts.push(password);
+        }
+        parts.push(if i % 2 == 1 { &digest } else { password });
+        digest = md5(&parts);
+    }
+
+    let mut encoded = String::new();
+    let mut push = |mut value: u32, count: usize| {
+        for _ in 0..count {
+            encoded.push(ITOA64[(value & 0x3f) as usize] as char);
+            value >>= 6;
+        }
+    };
+    for (a, b, c) in [(0, 6, 12), (1, 7, 13), (2, 8, 14), (3, 9, 15), (4, 10, 5)] {
+        let value =
+            (u32::from(digest[a]) << 16) | (u32::from(digest[b]) << 8) | u32::from(digest[c]);
+        push(value, 4);
+    }
+    push(u32::from(digest[11]), 2);
+
+    format!("$apr1${}${}", String::from_utf8_lossy(salt), encoded)
+}
+
+/// Returns a line of an htpasswd file with a random salt.
+pub fn create_htpasswd(
+    user: &str,
+    password: &str,
+    algorithm: HashAlgorithm,
+) -> Result<String, CustomError> {
+    let mut salt = [0; 16];
+    rand_bytes(&mut salt)
+        .map_err(|e| CustomError::new(format!("failed to create a salt, Error:{:?}", e)))?;
+    let hashed = match algorithm {
+        HashAlgorithm::Bcrypt => hash_bcrypt(password, salt)?,
+        HashAlgorithm::Apr1 => {
+            let salt: String = salt[..8]
+                .iter()
+                .map(|x| ITOA64[usize::from(x & 0x3f)] as char)
+                .collect();
+            hash_apr1(password, &salt)
+        }
+        HashAlgorithm::Sha => hash_sha(password),
+    };
+    Ok(format!("{}:{}", user, hashed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_reference_vectors() {
+        // salt "CCCCCCCCCCCCCCCCCCCCC." of the OpenBSD test vectors
+        let salt = [
+            0x10, 0x41, 0x04, 0x10, 0x41, 0x04, 0x10, 0x41, 0x04, 0x10, 0x41, 0x04, 0x10, 0x41,
+            0x04, 0x10,
+        ];
+        assert_eq!(
+            hash_bcrypt("U*U", salt).unwrap(),
+            "$2y$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW"
+        );
+        // `openssl passwd -apr1 -salt <salt> <password>`
+        assert_eq!(
+            hash_apr1("password", "r31....."),
+            "$apr1$r31.....$ARC3pREO82RIm0aQ2zszC0"
+        );
+        assert_eq!(
+            hash_apr1("p@ss:word", "saltsalt"),
+            "$apr1$saltsalt$nzzd7OghpzWlkaPWIMRgF1"
+        );
+        assert_eq!(hash_sha("password"), "{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=");
+    }
+
+    #[test]
+    fn test_create_htpasswd() {
+        let line = create_htpasswd("admin", "secret", HashAlgorithm::Bcrypt).unwrap();
+        let hashed = line.strip_prefix("admin:$2y$05$").expect(&line);
+        assert!(bcrypt::verify("secret", &format!("$2y$05${}", hashed)).unwrap());
+        assert_ne!(
+            line,
+            create_htpasswd("admin", "secret", HashAlgorithm::Bcrypt).unwrap()
+        );
+
+        let line = create_htpasswd("admin", "secret", HashAlgorithm::Apr1).unwrap();
+        let (_, hashed) = line.split_once(':').unwrap();
+        let salt = &hashed["$apr1$".len().."$apr1$".len() + 8];
+        assert_eq!(hashed, hash_apr1("secret", salt));
+
+        assert_eq!(
+            create_htpasswd("admin", "password", HashAlgorithm::Sha).unwrap(),
+            "admin:{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g="
+        );
+    }
+}
@@ -1,9 +1,9 @@
-use std::{
-    collections::{HashMap, HashSet},
-    path::PathBuf,
-};
+use std::{collections::HashMap, path::PathBuf};
 
+mod certificate;
 mod error;
+mod htpasswd;
+mod options;
 mod output;
 mod parse;
 mod templates;
@@ -11,7 +11,7 @@ mod utils;
 pub use output::output;
 pub use parse::parse;
 pub use templates::Config;
-use templates::Server;
+use templates::{Server, Stream};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum CacheType {
@@ -20,9 +20,75 @@ pub enum CacheType {
     Versioned,
 }
 
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub enum LoadBalancing {
+    #[default]
+    RoundRobin,
+    LeastConn,
+    IpHash,
+    Random,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SecurityPreset {
+    Basic,
+    Strict,
+}
+
+/// Hash of the generated htpasswd files, `{SHA}` is unsalted and only kept for compatibility.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum HashAlgorithm {
+    #[default]
+    Bcrypt,
+    Apr1,
+    Sha,
+}
+
+impl std::str::FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bcrypt" => Ok(HashAlgorithm::Bcrypt),
+            "apr1" => Ok(HashAlgorithm::Apr1),
+            "sha" => Ok(HashAlgorithm::Sha),
+            _ => Err(format!(
+                "unknown htpasswd hash '{}', expected one of bcrypt/apr1/sha",
+                s
+            )),
+        }
+    }
+}
+
+/// Secret of a basic auth user
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Password {
+    Plain(String),
+    /// an htpasswd hash, written as is
+    Hashed(String),
+}
+
+impl Password {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Password::Plain(x) | Password::Hashed(x) => x,
+        }
+    }
+}
+
+pub const DEFAULT_PORT: u16 = 80;
+
+/// (domain, listen port)
+pub type ServerKey = (String, u16);
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ParsedResult<'a> {
     target_dir: PathBuf,
-    basic_auth_map: HashSet<(String, String)>,
-    server_map: HashMap<String, Server<'a>>,
+    /// users of every generated htpasswd file
+    basic_auth_map: HashMap<String, Vec<(String, Password)>>,
+    server_map: HashMap<ServerKey, Server<'a>>,
+    self_signed_certificate: bool,
+    /// tcp/udp proxies, `None` when there are none
+    stream: Option<Stream<'a>>,
+    htpasswd_hash: HashAlgorithm,
 }
@@ -25,7 +25,22 @@ impl Args {
             .unwrap_or("false".to_string())
             == "true";
         Config {
-            docker_mode
+            docker_mode,
+            ssl_certificate: std::env::var("NGINX_SSL_CERTIFICATE").ok(),
+            ssl_certificate_key: std::env::var("NGINX_SSL_CERTIFICATE_KEY").ok(),
+            self_signed_certificate: std::env::var("NGINX_SELF_SIGNED_CERT")
+                .ok()
+                .unwrap_or("false".to_string())
+                == "true",
+            htpasswd_hash: std::env::var("NGINX_HTPASSWD_HASH")
+                .ok()
+                .map(|x| {
+                    x.parse().unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    })
+                })
+                .unwrap_or_default(),
         }
     }
     fn get_nginx_conf(&self) -> String {
@@ -92,9 +107,15 @@ fn main() {
         &nameserver,
         &hosts,
     )
-    .expect("parse failed");
+    .unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
 
-    output(&parsed_result).expect("output failed");
+    if let Err(e) = output(&parsed_result) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
 
 #[cfg(test)]
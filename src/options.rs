@@ -0,0 +1,1319 @@
+use std::{collections::HashSet, net::IpAddr};
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::{
+    error::Diagnostic,
+    templates::{AccessRule, Cors, Header, Headers, RateLimit},
+    utils::find_closest,
+    CacheType, LoadBalancing, Password, SecurityPreset,
+};
+
+pub const SOURCE_OPTIONS: &[&str] = &[
+    "file",
+    "sse",
+    "cert",
+    "cert-key",
+    "redirect-http",
+    "redirect-http-exempt",
+    "security",
+    "allow",
+    "deny",
+    "allow-file",
+    "deny-file",
+    "password-file",
+    "password-hash",
+    "htpasswd",
+    "users",
+    "realm",
+];
+
+pub const TARGET_OPTIONS: &[&str] = &[
+    "cache",
+    "versioned",
+    "must-revalidate",
+    "max-age",
+    "index",
+    "fallback",
+    "sse",
+    "lb",
+    "weight",
+    "max-fails",
+    "backup",
+    "root",
+    "ws",
+    "no-ws",
+    "add_header",
+    "add_header_always",
+    "proxy_header",
+    "hide_header",
+    "cors",
+    "cors-credentials",
+    "security",
+    "rate",
+    "burst",
+    "nodelay",
+    "rate-by",
+    "max_conn",
+    "limit_rate",
+    "allow",
+    "deny",
+    "allow-file",
+    "deny-file",
+    "satisfy",
+];
+
+/// Options whose value is a ',' separated list, a ',' in them does not start another backend.
+pub const LIST_OPTIONS: &[&str] = &["hide_header", "cors", "allow", "deny"];
+
+/// Options written as `<option>.<header name>=<value>`, a header value may contain ','.
+pub const HEADER_OPTIONS: &[&str] = &["add_header", "add_header_always", "proxy_header"];
+
+pub const BACKEND_OPTIONS: &[&str] = &["weight", "max-fails", "backup"];
+
+pub const REDIRECT_OPTIONS: &[&str] = &["keep-path", "keep-query"];
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct SourceOptions {
+    pub file: bool,
+    pub sse: bool,
+    pub certificate: Option<String>,
+    pub certificate_key: Option<String>,
+    pub redirect_http: bool,
+    pub redirect_http_exempt: Vec<String>,
+    /// preset for every route of the server
+    pub security: Option<SecurityPreset>,
+    /// `allow`/`deny` for every route of the server
+    pub access: Vec<AccessRule>,
+    /// password of the user in the url, from `password-file` or `password-hash`
+    pub password: Option<Password>,
+    /// existing htpasswd file used instead of the user in the url
+    pub htpasswd: Option<String>,
+    /// more users besides the one in the url
+    pub users: Vec<(String, Password)>,
+    /// name of the login prompt, routes with the same realm share their users
+    pub realm: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TargetOptions {
+    pub cache_type: CacheType,
+    pub max_age: Option<u32>,
+    pub index: bool,
+    pub fallback: bool,
+    pub sse: bool,
+    pub websocket: bool,
+    pub load_balancing: Option<LoadBalancing>,
+    /// document root of a fastcgi target
+    pub root: Option<String>,
+    pub headers: Headers,
+    pub cors: Option<Cors>,
+    pub security: Option<SecurityPreset>,
+    pub rate_limit: Option<RateLimit>,
+    pub max_conn: Option<u32>,
+    pub limit_rate: Option<String>,
+    pub access: Vec<AccessRule>,
+    pub satisfy_any: bool,
+    /// options of the last backend, the others carry their own `BackendOptions`
+    pub backend: BackendOptions,
+}
+
+impl Default for TargetOptions {
+    fn default() -> Self {
+        TargetOptions {
+            cache_type: CacheType::None,
+            max_age: None,
+            index: false,
+            fallback: false,
+            sse: false,
+            websocket: true,
+            load_balancing: None,
+            root: None,
+            headers: Headers::default(),
+            cors: None,
+            security: None,
+            rate_limit: None,
+            max_conn: None,
+            limit_rate: None,
+            access: vec![],
+            satisfy_any: false,
+            backend: BackendOptions::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct BackendOptions {
+    pub weight: Option<u32>,
+    pub max_fails: Option<u32>,
+    pub backup: bool,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct RedirectOptions {
+    pub keep_path: bool,
+    pub keep_query: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct OptionError {
+    pub key: String,
+    pub diagnostic: Diagnostic,
+}
+
+impl OptionError {
+    pub fn new(key: &str, message: impl Into<String>) -> Self {
+        OptionError {
+            key: key.to_string(),
+            diagnostic: Diagnostic::new(message),
+        }
+    }
+}
+
+fn query_pairs(url: &Url, errors: &mut Vec<OptionError>) -> Vec<(String, String)> {
+    let mut keys = HashSet::new();
+    let mut pairs = vec![];
+    let raw_values = url
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|x| !x.is_empty())
+        .map(|x| x.split_once('=').map_or("", |(_, value)| value));
+    for ((key, value), raw_value) in url.query_pairs().zip(raw_values) {
+        // decoded by parse_users after splitting on ',' and ':'
+        let value = if key == "users" {
+            raw_value.into()
+        } else {
+            value
+        };
+        if key.is_empty() {
+            errors.push(OptionError::new(
+                &format!("={}", value),
+                "empty option name",
+            ));
+        } else if !keys.insert(key.to_string()) {
+            errors.push(OptionError::new(
+                &key,
+                format!("option '{}' is set twice", key),
+            ));
+        } else {
+            pairs.push((key.into_owned(), value.into_owned()));
+        }
+    }
+    pairs
+}
+
+fn unknown_option(key: &str, known: &[&str], other: &[&str], side: &str) -> OptionError {
+    let mut error = OptionError::new(key, format!("unknown {} option '{}'", side, key));
+    if other.contains(&key) {
+        let other_side = if side == "source" { "right" } else { "left" };
+        error.diagnostic = error.diagnostic.with_help(format!(
+            "'{}' is only valid on the {} side of '>'",
+            key, other_side
+        ));
+    } else if let Some(closest) = find_closest(key, known) {
+        error.diagnostic = error
+            .diagnostic
+            .with_help(format!("did you mean '{}'?", closest));
+    }
+    error
+}
+
+fn parse_flag(key: &str, value: &str) -> Result<bool, OptionError> {
+    match value {
+        "" | "true" | "on" | "1" => Ok(true),
+        "false" | "off" | "0" => Ok(false),
+        _ => Err(OptionError::new(
+            key,
+            format!(
+                "option '{}' expects no value or one of true/false, got '{}'",
+                key, value
+            ),
+        )),
+    }
+}
+
+fn parse_cache_type(key: &str, value: &str) -> Result<CacheType, OptionError> {
+    match value {
+        "none" | "no-store" => Ok(CacheType::None),
+        "must-revalidate" => Ok(CacheType::MustRevalidate),
+        "versioned" => Ok(CacheType::Versioned),
+        _ => Err(OptionError::new(
+            key,
+            format!(
+                "option '{}' expects one of none/must-revalidate/versioned, got '{}'",
+                key, value
+            ),
+        )),
+    }
+}
+
+fn parse_security(key: &str, value: &str) -> Result<SecurityPreset, OptionError> {
+    match value {
+        "basic" => Ok(SecurityPreset::Basic),
+        "strict" => Ok(SecurityPreset::Strict),
+        _ => Err(OptionError::new(
+            key,
+            format!(
+                "option '{}' expects one of basic/strict, got '{}'",
+                key, value
+            ),
+        )),
+    }
+}
+
+/// Parses `<number>r/s` or `<number>r/m`.
+fn parse_rate(key: &str, value: &str) -> Result<String, OptionError> {
+    match value
+        .strip_suffix("r/s")
+        .or_else(|| value.strip_suffix("r/m"))
+        .map(|x| x.parse::<u32>())
+    {
+        Some(Ok(rate)) if rate > 0 => Ok(value.to_string()),
+        _ => Err(OptionError::new(
+            key,
+            format!(
+                "option '{}' expects requests per second or minute like '10r/s' or '30r/m', got '{}'",
+                key, value
+            ),
+        )),
+    }
+}
+
+/// Checks `s` is `all`, an address or a CIDR without host bits.
+fn check_cidr(s: &str) -> Result<(), String> {
+    if s == "all" {
+        return Ok(());
+    }
+    let (address, prefix) = s.split_once('/').unwrap_or((s, ""));
+    let Ok(address) = address.parse::<IpAddr>() else {
+        return Err(format!("invalid address '{}'", address));
+    };
+    let bits = match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    if prefix.is_empty() {
+        return Ok(());
+    }
+    let prefix = match prefix.parse::<u32>() {
+        Ok(prefix) if prefix <= bits => prefix,
+        _ => return Err(format!("invalid prefix length '{}'", prefix)),
+    };
+    let host_bits = match address {
+        IpAddr::V4(x) => u128::from(u32::from(x)) << 96,
+        IpAddr::V6(x) => u128::from(x),
+    }
+    .checked_shl(prefix)
+    .unwrap_or(0);
+    if host_bits != 0 {
+        return Err(format!("'{}' has host bits set", s));
+    }
+    Ok(())
+}
+
+fn parse_access_list(key: &str, value: &str) -> Result<Vec<String>, OptionError> {
+    value
+        .split(',')
+        .map(|x| {
+            let x = x.trim();
+            check_cidr(x).map(|_| x.to_string()).map_err(|e| {
+                OptionError::new(
+                    key,
+                    format!(
+                        "option '{}' expects CIDRs like '10.0.0.0/8' or 'all', {}",
+                        key, e
+                    ),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Reads one CIDR per line, `#` starts a comment.
+fn read_access_file(key: &str, path: &str) -> Result<Vec<String>, OptionError> {
+    let path = parse_path(key, path)?;
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| OptionError::new(key, format!("failed to read '{}': {}", path, e)))?;
+    let mut addresses = vec![];
+    for (index, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        check_cidr(line).map_err(|e| {
+            OptionError::new(key, format!("{} on line {} of '{}'", e, index + 1, path))
+        })?;
+        addresses.push(line.to_string());
+    }
+    Ok(addresses)
+}
+
+/// Appends the rules of `allow`, `deny`, `allow-file` or `deny-file` in the order of the options.
+fn parse_access(key: &str, value: &str, rules: &mut Vec<AccessRule>) -> Result<(), OptionError> {
+    let addresses = match key {
+        "allow-file" | "deny-file" => read_access_file(key, value)?,
+        _ => parse_access_list(key, value)?,
+    };
+    rules.extend(addresses.into_iter().map(|address| AccessRule {
+        allow: key.starts_with("allow"),
+        address,
+    }));
+    Ok(())
+}
+
+/// Reads a password from a file like a Docker secret, ignoring the trailing newline.
+fn read_password_file(key: &str, path: &str) -> Result<Password, OptionError> {
+    let path = parse_path(key, path)?;
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| OptionError::new(key, format!("failed to read '{}': {}", path, e)))?;
+    let password = content.trim_end_matches(['\n', '\r']);
+    if password.is_empty() {
+        return Err(OptionError::new(key, format!("'{}' is empty", path)));
+    }
+    Ok(Password::Plain(password.to_string()))
+}
+
+fn is_password_hash(value: &str) -> bool {
+    ["$2y$", "$2a$", "$2b$", "$apr1$", "{SHA}"]
+        .iter()
+        .any(|x| value.starts_with(x))
+}
+
+fn parse_password_hash(key: &str, value: &str) -> Result<Password, OptionError> {
+    if is_password_hash(value) && value.contains(' ') {
+        // a '+' of the hash was decoded as a space
+        Err(OptionError {
+            key: key.to_string(),
+            diagnostic: Diagnostic::new(format!(
+                "option '{}' has a space in the hash '{}'",
+                key, value
+            ))
+            .with_help("write '+' in the hash as '%2B'"),
+        })
+    } else if is_password_hash(value) {
+        Ok(Password::Hashed(value.to_string()))
+    } else {
+        Err(OptionError {
+            key: key.to_string(),
+            diagnostic: Diagnostic::new(format!(
+                "option '{}' expects a bcrypt, apr1 or {{SHA}} hash, got '{}'",
+                key, value
+            ))
+            .with_help("create one with 'htpasswd -nB <user>', and write '+' as '%2B'"),
+        })
+    }
+}
+
+/// Parses `user:password,...`, a password that looks like an htpasswd hash is kept as is.
+/// Like the password in the url, the parts are only percent-decoded and keep a literal `+`.
+fn parse_users(key: &str, value: &str) -> Result<Vec<(String, Password)>, OptionError> {
+    let decode = |x: &str| percent_decode_str(x).decode_utf8_lossy().into_owned();
+    value
+        .split(',')
+        .map(|x| {
+            let pair = x.split_once(':').map(|(u, p)| (decode(u), decode(p)));
+            match pair {
+                Some((user, password)) if !user.is_empty() && !password.is_empty() => Ok((
+                    user,
+                    if is_password_hash(&password) {
+                        parse_password_hash(key, &password)?
+                    } else {
+                        Password::Plain(password)
+                    },
+                )),
+                _ => Err(OptionError::new(
+                    key,
+                    format!("option '{}' expects 'user:password', got '{}'", key, x),
+                )),
+            }
+        })
+        .collect()
+}
+
+fn parse_realm(key: &str, value: &str) -> Result<String, OptionError> {
+    if value.trim().is_empty() {
+        Err(OptionError::new(
+            key,
+            format!("option '{}' expects a name", key),
+        ))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// Parses a size in bytes with an optional `k` or `m` suffix, e.g. `500k`.
+fn parse_size(key: &str, value: &str) -> Result<String, OptionError> {
+    let number = value.strip_suffix(['k', 'K', 'm', 'M']).unwrap_or(value);
+    match number.parse::<u32>() {
+        Ok(size) if size > 0 => Ok(value.to_ascii_lowercase()),
+        _ => Err(OptionError::new(
+            key,
+            format!(
+                "option '{}' expects a size like '500k' or '1m', got '{}'",
+                key, value
+            ),
+        )),
+    }
+}
+
+/// Parses `ip` or a request header name into the key of a zone.
+fn parse_zone_key(key: &str, value: &str) -> Result<String, OptionError> {
+    if value == "ip" {
+        return Ok("$binary_remote_addr".to_string());
+    }
+    let name = parse_header_name(key, value)?;
+    Ok(format!(
+        "$http_{}",
+        name.to_ascii_lowercase().replace('-', "_")
+    ))
+}
+
+/// Returns true for `any`.
+fn parse_satisfy(key: &str, value: &str) -> Result<bool, OptionError> {
+    match value {
+        "any" => Ok(true),
+        "all" => Ok(false),
+        _ => Err(OptionError::new(
+            key,
+            format!("option '{}' expects one of any/all, got '{}'", key, value),
+        )),
+    }
+}
+
+fn parse_load_balancing(key: &str, value: &str) -> Result<LoadBalancing, OptionError> {
+    match value.replace('-', "_").as_str() {
+        "round_robin" => Ok(LoadBalancing::RoundRobin),
+        "least_conn" => Ok(LoadBalancing::LeastConn),
+        "ip_hash" => Ok(LoadBalancing::IpHash),
+        "random" => Ok(LoadBalancing::Random),
+        _ => Err(OptionError::new(
+            key,
+            format!(
+                "option '{}' expects one of round_robin/least_conn/ip_hash/random, got '{}'",
+                key, value
+            ),
+        )),
+    }
+}
+
+fn parse_header_name(key: &str, name: &str) -> Result<String, OptionError> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(name.to_string())
+    } else {
+        Err(OptionError::new(
+            key,
+            format!("option '{}' expects a header name, got '{}'", key, name),
+        ))
+    }
+}
+
+/// Parses `add_header.<name>`, `add_header_always.<name>` and `proxy_header.<name>`.
+fn parse_header(key: &str, value: &str, headers: &mut Headers) -> Result<(), OptionError> {
+    let (prefix, name) = key.split_once('.').unwrap_or((key, ""));
+    let list = match prefix {
+        "add_header" | "add_header_always" => &mut headers.add,
+        "proxy_header" => &mut headers.proxy,
+        _ => {
+            let mut error = unknown_option(prefix, TARGET_OPTIONS, SOURCE_OPTIONS, "target");
+            error.key = key.to_string();
+            return Err(error);
+        }
+    };
+    if name.is_empty() {
+        return Err(OptionError::new(
+            key,
+            format!(
+                "option '{}' expects a header name, e.g. '{}.X-Frame-Options=DENY'",
+                prefix, prefix
+            ),
+        ));
+    }
+    list.push(Header {
+        name: parse_header_name(key, name)?,
+        value: value.to_string(),
+        always: prefix == "add_header_always",
+    });
+    Ok(())
+}
+
+/// Parses `scheme://host[:port]`, the first label of host can be `*` to match any subdomain.
+fn parse_origin(key: &str, origin: &str) -> Result<String, OptionError> {
+    let origin = origin.to_ascii_lowercase();
+    let is_valid = match origin.split_once("://") {
+        Some(("http" | "https", rest)) => {
+            let (host, port) = rest.split_once(':').unwrap_or((rest, "80"));
+            let host = host.strip_prefix("*.").unwrap_or(host);
+            port.parse::<u16>().is_ok()
+                && host.split('.').all(|label| {
+                    !label.is_empty()
+                        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                })
+        }
+        _ => false,
+    };
+    if is_valid {
+        Ok(origin)
+    } else {
+        Err(OptionError {
+            key: key.to_string(),
+            diagnostic: Diagnostic::new(format!(
+                "option '{}' expects origins like 'https://example.com', got '{}'",
+                key, origin
+            ))
+            .with_help("write '*' to allow any origin, or 'https://*.example.com' for subdomains"),
+        })
+    }
+}
+
+fn parse_cors(key: &str, value: &str) -> Result<Cors, OptionError> {
+    let origins = if value == "*" {
+        vec!["*".to_string()]
+    } else {
+        value
+            .split(',')
+            .map(|x| parse_origin(key, x.trim()))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    Ok(Cors {
+        origins,
+        credentials: false,
+        map: None,
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, OptionError> {
+    value.parse().map_err(|_| {
+        OptionError::new(
+            key,
+            format!("option '{}' expects a number, got '{}'", key, value),
+        )
+    })
+}
+
+fn parse_path(key: &str, value: &str) -> Result<String, OptionError> {
+    if value.starts_with('/') {
+        Ok(value.to_string())
+    } else {
+        Err(OptionError::new(
+            key,
+            format!("option '{}' expects an absolute path, got '{}'", key, value),
+        ))
+    }
+}
+
+/// Stream routes take no options, every key is reported as unknown.
+pub fn check_stream_options(url: &Url) -> Result<(), Vec<OptionError>> {
+    let mut errors = vec![];
+    for (key, _) in query_pairs(url, &mut errors) {
+        errors.push(unknown_option(&key, &[], &[], "stream"));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+impl SourceOptions {
+    pub fn from_url(url: &Url) -> Result<Self, Vec<OptionError>> {
+        let mut options = SourceOptions::default();
+        let mut errors = vec![];
+        for (key, value) in query_pairs(url, &mut errors) {
+            let key = key.as_str();
+            let result = match key {
+                "file" => parse_flag(key, &value).map(|v| options.file = v),
+                "sse" => parse_flag(key, &value).map(|v| options.sse = v),
+                "cert" => parse_path(key, &value).map(|v| options.certificate = Some(v)),
+                "cert-key" => parse_path(key, &value).map(|v| options.certificate_key = Some(v)),
+                "redirect-http" => parse_flag(key, &value).map(|v| options.redirect_http = v),
+                "redirect-http-exempt" => value
+                    .split(',')
+                    .map(|x| parse_path(key, x.trim()))
+                    .collect::<Result<_, _>>()
+                    .map(|v| {
+                        options.redirect_http = true;
+                        options.redirect_http_exempt = v;
+                    }),
+                "security" => parse_security(key, &value).map(|v| options.security = Some(v)),
+                "allow" | "deny" | "allow-file" | "deny-file" => {
+                    parse_access(key, &value, &mut options.access)
+                }
+                "password-file" | "password-hash" if options.password.is_some() => {
+                    Err(OptionError::new(
+                        key,
+                        format!("option '{}' conflicts with another password option", key),
+                    ))
+                }
+                "password-file" => {
+                    read_password_file(key, &value).map(|v| options.password = Some(v))
+                }
+                "password-hash" => {
+                    parse_password_hash(key, &value).map(|v| options.password = Some(v))
+                }
+                "htpasswd" => parse_path(key, &value).map(|v| options.htpasswd = Some(v)),
+                "users" => parse_users(key, &value).map(|v| options.users = v),
+                "realm" => parse_realm(key, &value).map(|v| options.realm = Some(v)),
+                _ => Err(unknown_option(
+                    key,
+                    SOURCE_OPTIONS,
+                    TARGET_OPTIONS,
+                    "source",
+                )),
+            };
+            if let Err(e) = result {
+                errors.push(e);
+            }
+        }
+        if errors.is_empty() {
+            Ok(options)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl TargetOptions {
+    pub fn from_url(url: &Url) -> Result<Self, Vec<OptionError>> {
+        let mut options = TargetOptions::default();
+        let mut errors = vec![];
+        let mut cache_type = None;
+        let mut websocket = None;
+        let mut credentials = false;
+        let mut rate = None;
+        let mut burst = None;
+        let mut nodelay = false;
+        let mut rate_key = None;
+        for (key, value) in query_pairs(url, &mut errors) {
+            let key = key.as_str();
+            let result = match key {
+                "cache" | "versioned" | "must-revalidate" if cache_type.is_some() => {
+                    Err(OptionError::new(
+                        key,
+                        format!("option '{}' conflicts with another cache option", key),
+                    ))
+                }
+                "cache" => parse_cache_type(key, &value).map(|v| cache_type = Some(v)),
+                // shorthands kept for `?versioned` / `?must-revalidate`
+                "versioned" | "must-revalidate" if !value.is_empty() => Err(OptionError::new(
+                    key,
+                    format!("option '{}' does not take a value, got '{}'", key, value),
+                )),
+                "versioned" | "must-revalidate" => {
+                    parse_cache_type(key, key).map(|v| cache_type = Some(v))
+                }
+                "max-age" => parse_number(key, &value).map(|v| options.max_age = Some(v)),
+                "index" => parse_flag(key, &value).map(|v| options.index = v),
+                "fallback" => parse_flag(key, &value).map(|v| options.fallback = v),
+                "sse" => parse_flag(key, &value).map(|v| options.sse = v),
+                "ws" | "no-ws" if websocket.is_some() => Err(OptionError::new(
+                    key,
+                    format!("option '{}' conflicts with another websocket option", key),
+                )),
+                "ws" => parse_flag(key, &value).map(|v| websocket = Some(v)),
+                // shorthand kept for `?ws=false`
+                "no-ws" => parse_flag(key, &value).map(|v| websocket = Some(!v)),
+                "lb" => parse_load_balancing(key, &value).map(|v| options.load_balancing = Some(v)),
+                "root" => parse_path(key, &value).map(|v| options.root = Some(v)),
+                "weight" | "max-fails" | "backup" => options.backend.parse_option(key, &value),
+                "hide_header" => value
+                    .split(',')
+                    .map(|x| parse_header_name(key, x.trim()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|v| options.headers.hide = v),
+                "add_header" | "add_header_always" | "proxy_header" => {
+                    parse_header(key, &value, &mut options.headers)
+                }
+                "security" => parse_security(key, &value).map(|v| options.security = Some(v)),
+                "rate" => parse_rate(key, &value).map(|v| rate = Some(v)),
+                "burst" => parse_number(key, &value).map(|v| burst = Some(v)),
+                "nodelay" => parse_flag(key, &value).map(|v| nodelay = v),
+                "rate-by" => parse_zone_key(key, &value).map(|v| rate_key = Some(v)),
+                "max_conn" => parse_number(key, &value).and_then(|v| match v {
+                    0 => Err(OptionError::new(
+                        key,
+                        "option 'max_conn' must be at least 1",
+                    )),
+                    v => {
+                        options.max_conn = Some(v);
+                        Ok(())
+                    }
+                }),
+                "limit_rate" => parse_size(key, &value).map(|v| options.limit_rate = Some(v)),
+                "allow" | "deny" | "allow-file" | "deny-file" => {
+                    parse_access(key, &value, &mut options.access)
+                }
+                "satisfy" => parse_satisfy(key, &value).map(|v| options.satisfy_any = v),
+                "cors" => parse_cors(key, &value).map(|v| options.cors = Some(v)),
+                "cors-credentials" => parse_flag(key, &value).map(|v| credentials = v),
+                _ if key.contains('.') => parse_header(key, &value, &mut options.headers),
+                _ => Err(unknown_option(
+                    key,
+                    TARGET_OPTIONS,
+                    SOURCE_OPTIONS,
+                    "target",
+                )),
+            };
+            if let Err(e) = result {
+                errors.push(e);
+            }
+        }
+        options.websocket = websocket.unwrap_or(true);
+        match rate {
+            Some(rate) => {
+                options.rate_limit = Some(RateLimit {
+                    zone: String::new(),
+                    key: rate_key.unwrap_or_else(|| "$binary_remote_addr".to_string()),
+                    rate,
+                    burst,
+                    nodelay,
+                })
+            }
+            None => {
+                for (key, is_set) in [
+                    ("burst", burst.is_some()),
+                    ("nodelay", nodelay),
+                    ("rate-by", rate_key.is_some()),
+                ] {
+                    if is_set {
+                        errors.push(OptionError::new(
+                            key,
+                            format!("option '{}' requires 'rate'", key),
+                        ));
+                    }
+                }
+            }
+        }
+        match &mut options.cors {
+            Some(cors) if credentials && cors.origins == ["*"] => errors.push(OptionError {
+                key: "cors-credentials".to_string(),
+                diagnostic: Diagnostic::new(
+                    "option 'cors-credentials' can not be used with 'cors=*'",
+                )
+                .with_help("list the allowed origins, e.g. 'cors=https://app.example.com'"),
+            }),
+            Some(cors) => cors.credentials = credentials,
+            None if credentials => errors.push(OptionError::new(
+                "cors-credentials",
+                "option 'cors-credentials' requires 'cors'",
+            )),
+            None => {}
+        }
+        options.cache_type = match (cache_type, options.max_age) {
+            (None, None) => CacheType::None,
+            (None, Some(_)) | (Some(CacheType::Versioned), _) => CacheType::Versioned,
+            (Some(cache_type), None) => cache_type,
+            (Some(cache_type), Some(_)) => {
+                errors.push(OptionError::new(
+                    "max-age",
+                    "option 'max-age' can only be used with 'cache=versioned'",
+                ));
+                cache_type
+            }
+        };
+        if errors.is_empty() {
+            Ok(options)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl BackendOptions {
+    /// Parses the options of a backend followed by other backends of the same group.
+    pub fn from_url(url: &Url) -> Result<Self, Vec<OptionError>> {
+        let mut options = BackendOptions::default();
+        let mut errors = vec![];
+        for (key, value) in query_pairs(url, &mut errors) {
+            let key = key.as_str();
+            let result = if BACKEND_OPTIONS.contains(&key) {
+                options.parse_option(key, &value)
+            } else if TARGET_OPTIONS.contains(&key) {
+                let error =
+                    OptionError::new(key, format!("option '{}' applies to the whole route", key));
+                Err(OptionError {
+                    diagnostic: error.diagnostic.with_help("set it after the last backend"),
+                    ..error
+                })
+            } else {
+                Err(unknown_option(
+                    key,
+                    BACKEND_OPTIONS,
+                    SOURCE_OPTIONS,
+                    "backend",
+                ))
+            };
+            if let Err(e) = result {
+                errors.push(e);
+            }
+        }
+        if errors.is_empty() {
+            Ok(options)
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == BackendOptions::default()
+    }
+
+    fn parse_option(&mut self, key: &str, value: &str) -> Result<(), OptionError> {
+        match key {
+            "weight" => parse_number(key, value).and_then(|v| match v {
+                0 => Err(OptionError::new(key, "option 'weight' must be at least 1")),
+                v => {
+                    self.weight = Some(v);
+                    Ok(())
+                }
+            }),
+            "max-fails" => parse_number(key, value).map(|v| self.max_fails = Some(v)),
+            "backup" => parse_flag(key, value).map(|v| self.backup = v),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl RedirectOptions {
+    pub fn from_url(url: &Url) -> Result<Self, Vec<OptionError>> {
+        let mut options = RedirectOptions::default();
+        let mut errors = vec![];
+        for (key, value) in query_pairs(url, &mut errors) {
+            let key = key.as_str();
+            let result = match key {
+                "keep-path" => parse_flag(key, &value).map(|v| options.keep_path = v),
+                "keep-query" => parse_flag(key, &value).map(|v| options.keep_query = v),
+                _ => Err(unknown_option(key, REDIRECT_OPTIONS, &[], "redirect")),
+            };
+            if let Err(e) = result {
+                errors.push(e);
+            }
+        }
+        if errors.is_empty() {
+            Ok(options)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse("file://*").unwrap().join(s).unwrap()
+    }
+
+    #[test]
+    fn test_source_options() {
+        for (s, expected) in [
+            ("/", SourceOptions::default()),
+            (
+                "/?file",
+                SourceOptions {
+                    file: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                "/?sse=true&file=false",
+                SourceOptions {
+                    sse: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                "/?cert=/etc/ssl/a.pem&cert-key=/etc/ssl/a.key",
+                SourceOptions {
+                    certificate: Some("/etc/ssl/a.pem".to_string()),
+                    certificate_key: Some("/etc/ssl/a.key".to_string()),
+                    ..Default::default()
+                },
+            ),
+            (
+                "/?redirect-http-exempt=/.well-known/,/health",
+                SourceOptions {
+                    redirect_http: true,
+                    redirect_http_exempt: vec!["/.well-known/".to_string(), "/health".to_string()],
+                    ..Default::default()
+                },
+            ),
+            (
+                "/?security=strict",
+                SourceOptions {
+                    security: Some(SecurityPreset::Strict),
+                    ..Default::default()
+                },
+            ),
+        ] {
+            assert_eq!(SourceOptions::from_url(&url(s)).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_target_options() {
+        for (s, expected) in [
+            ("/", TargetOptions::default()),
+            (
+                "/?versioned",
+                TargetOptions {
+                    cache_type: CacheType::Versioned,
+                    ..Default::default()
+                },
+            ),
+            (
+                "/?cache=versioned&max-age=3600&index",
+                TargetOptions {
+                    cache_type: CacheType::Versioned,
+                    max_age: Some(3600),
+                    index: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                "/?max-age=60",
+                TargetOptions {
+                    cache_type: CacheType::Versioned,
+                    max_age: Some(60),
+                    ..Default::default()
+                },
+            ),
+            (
+                "/?security=basic",
+                TargetOptions {
+                    security: Some(SecurityPreset::Basic),
+                    ..Default::default()
+                },
+            ),
+            (
+                "/?rate=30r/m&burst=5&nodelay&rate-by=X-Api-Key",
+                TargetOptions {
+                    rate_limit: Some(RateLimit {
+                        zone: "".to_string(),
+                        key: "$http_x_api_key".to_string(),
+                        rate: "30r/m".to_string(),
+                        burst: Some(5),
+                        nodelay: true,
+                    }),
+                    ..Default::default()
+                },
+            ),
+            (
+                "/?max_conn=2&limit_rate=500K",
+                TargetOptions {
+                    max_conn: Some(2),
+                    limit_rate: Some("500k".to_string()),
+                    ..Default::default()
+                },
+            ),
+            (
+                "/?no-ws",
+                TargetOptions {
+                    websocket: false,
+                    ..Default::default()
+                },
+            ),
+            (
+                "/?cache=must-revalidate&fallback",
+                TargetOptions {
+                    cache_type: CacheType::MustRevalidate,
+                    fallback: true,
+                    ..Default::default()
+                },
+            ),
+        ] {
+            assert_eq!(TargetOptions::from_url(&url(s)).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_backend_options() {
+        let options = TargetOptions::from_url(&url("/?lb=least_conn&weight=2&backup&sse"))
+            .expect("failed to parse target options");
+        assert_eq!(options.load_balancing, Some(LoadBalancing::LeastConn));
+        assert_eq!(
+            options.backend,
+            BackendOptions {
+                weight: Some(2),
+                max_fails: None,
+                backup: true,
+            }
+        );
+        assert_eq!(
+            BackendOptions::from_url(&url("/?max-fails=3")).unwrap(),
+            BackendOptions {
+                max_fails: Some(3),
+                ..Default::default()
+            }
+        );
+        for s in [
+            "/?lb=fastest",
+            "/?weight=0",
+            "/?max-fails=-1",
+            "/?lb=random",
+            "/?sse",
+        ] {
+            assert!(BackendOptions::from_url(&url(s)).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_header_options() {
+        let options = TargetOptions::from_url(&url(
+            "/?add_header.X-Foo=bar&add_header_always.Cache-Control=no-cache,%20private&proxy_header.X-Tenant=acme&hide_header=Server,X-Powered-By",
+        ))
+        .expect("failed to parse header options");
+        assert_eq!(
+            options.headers,
+            Headers {
+                add: vec![
+                    Header {
+                        name: "X-Foo".to_string(),
+                        value: "bar".to_string(),
+                        always: false,
+                    },
+                    Header {
+                        name: "Cache-Control".to_string(),
+                        value: "no-cache, private".to_string(),
+                        always: true,
+                    },
+                ],
+                proxy: vec![Header {
+                    name: "X-Tenant".to_string(),
+                    value: "acme".to_string(),
+                    always: false,
+                }],
+                hide: vec!["Server".to_string(), "X-Powered-By".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_cors_options() {
+        for (s, origins, credentials) in [
+            ("/?cors=*", vec!["*"], false),
+            (
+                "/?cors=https://App.example.com,http://*.example.com:8080&cors-credentials",
+                vec!["https://app.example.com", "http://*.example.com:8080"],
+                true,
+            ),
+        ] {
+            let cors = TargetOptions::from_url(&url(s))
+                .expect(s)
+                .cors
+                .expect("cors must be set");
+            assert_eq!(cors.origins, origins, "{}", s);
+            assert_eq!(cors.credentials, credentials, "{}", s);
+        }
+        for s in [
+            "/?cors",
+            "/?cors=example.com",
+            "/?cors=https://example.com/app",
+            "/?cors=*,https://example.com",
+            "/?cors=https://app.*.com",
+            "/?cors-credentials",
+            "/?cors=*&cors-credentials",
+        ] {
+            assert!(TargetOptions::from_url(&url(s)).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_access_options() {
+        let path = std::env::temp_dir().join("ee-nginx-test-access.txt");
+        std::fs::write(&path, "# office\n10.1.0.0/16\n\n2001:db8::/32 # vpn\n").unwrap();
+        let options = TargetOptions::from_url(&url(&format!(
+            "/?allow=127.0.0.1,::1&allow-file={}&deny=all",
+            path.to_str().unwrap()
+        )))
+        .expect("failed to parse access options");
+        assert_eq!(
+            options
+                .access
+                .iter()
+                .map(|x| (x.allow, x.address.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (true, "127.0.0.1"),
+                (true, "::1"),
+                (true, "10.1.0.0/16"),
+                (true, "2001:db8::/32"),
+                (false, "all"),
+            ]
+        );
+
+        std::fs::write(&path, "10.1.0.0/16\n10.2.0.1/16\n").unwrap();
+        let errors =
+            SourceOptions::from_url(&url(&format!("/?deny-file={}", path.to_str().unwrap())))
+                .expect_err("host bits are set");
+        assert_eq!(
+            errors[0].diagnostic.message,
+            format!(
+                "'10.2.0.1/16' has host bits set on line 2 of '{}'",
+                path.to_str().unwrap()
+            )
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        for s in [
+            "/?allow=10.0.0.0/33",
+            "/?allow=10.0.0.1/8",
+            "/?allow=2001:db8::1/32",
+            "/?deny=everyone",
+            "/?allow=10.0.0.0/8,",
+            "/?allow-file=relative.txt",
+            "/?allow-file=/nonexistent/ee-nginx.txt",
+        ] {
+            assert!(TargetOptions::from_url(&url(s)).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_password_options() {
+        let path = std::env::temp_dir().join("ee-nginx-test-password");
+        std::fs::write(&path, "s3cret\n").unwrap();
+        let options =
+            SourceOptions::from_url(&url(&format!("/?password-file={}", path.to_str().unwrap())))
+                .expect("failed to read password file");
+        assert_eq!(
+            options.password,
+            Some(Password::Plain("s3cret".to_string()))
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        let options = SourceOptions::from_url(&url(
+            "/?password-hash=$apr1$r31.....$ARC3pREO82RIm0aQ2zszC0",
+        ))
+        .expect("failed to parse password hash");
+        assert_eq!(
+            options.password,
+            Some(Password::Hashed(
+                "$apr1$r31.....$ARC3pREO82RIm0aQ2zszC0".to_string()
+            ))
+        );
+        let options = SourceOptions::from_url(&url("/?htpasswd=/run/secrets/htpasswd"))
+            .expect("failed to parse htpasswd");
+        assert_eq!(options.htpasswd.as_deref(), Some("/run/secrets/htpasswd"));
+        let options = SourceOptions::from_url(&url(
+            "/?users=bob:s3+cret,carol:{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=,dave:a%3Ab%2Cc&realm=Team%20area",
+        ))
+        .expect("failed to parse users");
+        assert_eq!(
+            options.users,
+            vec![
+                ("bob".to_string(), Password::Plain("s3+cret".to_string())),
+                (
+                    "carol".to_string(),
+                    Password::Hashed("{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=".to_string())
+                ),
+                ("dave".to_string(), Password::Plain("a:b,c".to_string())),
+            ]
+        );
+        assert_eq!(options.realm.as_deref(), Some("Team area"));
+
+        for s in [
+            "/?password-hash=secret",
+            "/?password-file=/nonexistent/ee-nginx-password",
+            "/?password-file=secret.txt",
+            "/?password-hash=$apr1$r31.....$ARC3pREO82RIm0aQ2zszC0&password-file=/run/secrets/a",
+            "/?htpasswd=htpasswd",
+            "/?users=bob",
+            "/?users=bob:{SHA}abc%20def=",
+            "/?password-hash={SHA}abc+def=",
+            "/?users=bob:s3cret,:s3cret",
+            "/?realm=",
+        ] {
+            assert!(SourceOptions::from_url(&url(s)).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_target_options_malformed() {
+        for s in [
+            "/?index=maybe",
+            "/?cache=forever",
+            "/?max-age=1y",
+            "/?max-age=60&cache=none",
+            "/?index&index",
+            "/?versioned&cache=none",
+            "/?versioned=1",
+            "/?ws&no-ws",
+            "/?=1",
+            "/?reindex",
+            "/?add_header.=1",
+            "/?add_header.X%20Foo=1",
+            "/?hide_header=Server,",
+            "/?security=paranoid",
+            "/?rate=10",
+            "/?rate=0r/s",
+            "/?rate=10r/h",
+            "/?burst=20",
+            "/?rate=10r/s&rate-by=X%20Key",
+            "/?max_conn=0",
+            "/?limit_rate=1g",
+            "/?limit_rate=0",
+            "/?satisfy=some",
+        ] {
+            assert!(TargetOptions::from_url(&url(s)).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_unknown_options() {
+        for (s, message, help) in [
+            (
+                "/?versionned",
+                "unknown target option 'versionned'",
+                Some("did you mean 'versioned'?"),
+            ),
+            (
+                "/?fallbak",
+                "unknown target option 'fallbak'",
+                Some("did you mean 'fallback'?"),
+            ),
+            (
+                "/?file",
+                "unknown target option 'file'",
+                Some("'file' is only valid on the left side of '>'"),
+            ),
+            ("/?gzip", "unknown target option 'gzip'", None),
+            (
+                "/?add_heder.X-Frame-Options=DENY",
+                "unknown target option 'add_heder'",
+                Some("did you mean 'add_header'?"),
+            ),
+            (
+                "/?proxy_header=acme",
+                "option 'proxy_header' expects a header name, e.g. 'proxy_header.X-Frame-Options=DENY'",
+                None,
+            ),
+        ] {
+            let errors = TargetOptions::from_url(&url(s)).expect_err(s);
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].diagnostic.message, message);
+            assert_eq!(errors[0].diagnostic.help.as_deref(), help);
+        }
+        let errors = SourceOptions::from_url(&url("/?fiel")).expect_err("fiel");
+        assert_eq!(errors[0].key, "fiel");
+        assert_eq!(
+            errors[0].diagnostic.help.as_deref(),
+            Some("did you mean 'file'?")
+        );
+    }
+
+    #[test]
+    fn test_collects_all_errors() {
+        let errors = TargetOptions::from_url(&url("/?versionned&index=maybe&fallback"))
+            .expect_err("two bad options");
+        assert_eq!(
+            errors.iter().map(|e| e.key.as_str()).collect::<Vec<_>>(),
+            vec!["versionned", "index"]
+        );
+    }
+}
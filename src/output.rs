@@ -1,23 +1,52 @@
-use std::{io::Write, path::Path};
+use std::{
+    collections::HashMap,
+    fs::{OpenOptions, Permissions},
+    io::Write,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::Path,
+};
 
 use askama::Template;
-use base64::{engine::general_purpose, Engine};
-use log::debug;
-use openssl::hash::{Hasher, MessageDigest};
+use log::{debug, warn};
 
-use crate::{error::CustomError, utils::get_basic_auth_file_path, ParsedResult};
+use crate::{
+    certificate::{create_self_signed_certificate, get_certificate_domains},
+    error::CustomError,
+    htpasswd::create_htpasswd,
+    templates::{Http, Location, Server},
+    utils::get_self_signed_certificate_paths,
+    ParsedResult, Password, ServerKey, DEFAULT_PORT,
+};
 
-fn create_htpasswd(user: &str, password: &str) -> String {
-    let mut hasher = Hasher::new(MessageDigest::sha1()).unwrap();
-    hasher.update(password.as_bytes()).unwrap();
-    let digest = hasher.finish().unwrap();
+/// Contains a '.' before `.conf`, so it can not clash with a server file name.
+const HTTP_FILE_NAME: &str = "ee-nginx.http.conf";
 
-    let hashed = general_purpose::STANDARD.encode(digest);
+/// Included from the main context of nginx.conf, so it must not match the `*.conf` of `http`.
+const STREAM_FILE_NAME: &str = "default.stream";
 
-    format!("{}:{{SHA}}{}", user, hashed)
+fn get_server_file_name((domain, port): &ServerKey) -> String {
+    let name = if domain == "*" {
+        "default".to_string()
+    } else {
+        domain.replace('.', "_")
+    };
+    if *port == DEFAULT_PORT {
+        format!("{}.conf", name)
+    } else {
+        format!("{}_{}.conf", name, port)
+    }
 }
 
 fn write_to_file(dst_path: &Path, content: &str) -> Result<(), CustomError> {
+    write_with_mode(dst_path, content, None)
+}
+
+/// Writes a file only the owner can read, like a private key.
+fn write_private_file(dst_path: &Path, content: &str) -> Result<(), CustomError> {
+    write_with_mode(dst_path, content, Some(0o600))
+}
+
+fn write_with_mode(dst_path: &Path, content: &str, mode: Option<u32>) -> Result<(), CustomError> {
     if dst_path.to_str().unwrap().starts_with("-/") {
         println!("----- {} -----", &dst_path.to_str().unwrap()[2..]);
         println!("{}", content);
@@ -25,9 +54,24 @@ fn write_to_file(dst_path: &Path, content: &str) -> Result<(), CustomError> {
         debug!("// ---------- {:?} ----------", dst_path);
         debug!("{}", content);
 
-        let mut file = std::fs::File::create(dst_path).map_err(|e| {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        if let Some(mode) = mode {
+            options.mode(mode);
+        }
+        let mut file = options.open(dst_path).map_err(|e| {
             CustomError::new(format!("failed to crete file. {:?}, {:?}", dst_path, e))
         })?;
+        if let Some(mode) = mode {
+            // the mode of `open` only applies to new files
+            file.set_permissions(Permissions::from_mode(mode))
+                .map_err(|e| {
+                    CustomError::new(format!(
+                        "set_permissions failed for path {:?}, Error:{:?}",
+                        dst_path, e
+                    ))
+                })?;
+        }
         file.write_all(content.as_bytes()).map_err(|e| {
             CustomError::new(format!(
                 "write_all failed for path {:?}, Error:{:?}",
@@ -38,23 +82,197 @@ fn write_to_file(dst_path: &Path, content: &str) -> Result<(), CustomError> {
     Ok(())
 }
 
+fn write_self_signed_certificate(parsed_result: &ParsedResult) -> Result<(), CustomError> {
+    let mut domains: Vec<String> = parsed_result
+        .server_map
+        .keys()
+        .map(|(domain, _)| {
+            if domain == "*" {
+                "localhost".to_string()
+            } else {
+                domain.clone()
+            }
+        })
+        .collect();
+    domains.sort();
+    domains.dedup();
+
+    let (certificate_path, key_path) = get_self_signed_certificate_paths(&parsed_result.target_dir);
+    if key_path.exists() {
+        if let Some(existing) = std::fs::read(&certificate_path)
+            .ok()
+            .and_then(|pem| get_certificate_domains(&pem))
+        {
+            if domains.iter().all(|x| existing.contains(x)) {
+                debug!("reuse self-signed certificate {:?}", certificate_path);
+                return Ok(());
+            }
+        }
+    }
+
+    let (certificate, key) = create_self_signed_certificate(&domains)?;
+    write_to_file(&certificate_path, &certificate)?;
+    write_private_file(&key_path, &key)
+}
+
+/// Copies `location` to serve only `path` under it, keeping the target path in sync.
+fn narrow_location<'a>(location: &Location<'a>, path: &str) -> Location<'a> {
+    let rest = &path[location.location.len()..];
+    let alias = if location.alias.is_empty() {
+        String::new()
+    } else if location.alias.ends_with('/') {
+        format!("{}{}", location.alias, rest.trim_start_matches('/'))
+    } else {
+        format!("{}{}", location.alias, rest)
+    };
+    Location {
+        location: path.to_string(),
+        alias,
+        ..location.clone()
+    }
+}
+
+/// Adds a port 80 server redirecting to HTTPS for every TLS server requesting it.
+/// Exempt paths are served on HTTP as well, by the locations under them and the longest
+/// location containing them.
+fn add_https_redirects(server_map: &mut HashMap<ServerKey, Server>) {
+    let mut redirects = vec![];
+    for ((domain, port), server) in server_map.iter() {
+        let Some(exempt) = server.tls.as_ref().and_then(|x| x.redirect_http.as_ref()) else {
+            continue;
+        };
+        let mut locations = vec![];
+        for path in exempt {
+            let parent = server
+                .locations
+                .iter()
+                .filter(|x| path.starts_with(&x.location))
+                .max_by_key(|x| x.location.len())
+                .filter(|x| x.location != *path)
+                .map(|x| narrow_location(x, path));
+            let matched: Vec<_> = parent
+                .into_iter()
+                .chain(
+                    server
+                        .locations
+                        .iter()
+                        .filter(|x| x.location.starts_with(path.as_str()))
+                        .cloned(),
+                )
+                .map(|x| Location {
+                    // the upstream block is already written with the https server
+                    upstream: None,
+                    ..x
+                })
+                .collect();
+            if matched.is_empty() {
+                warn!("no route for '{}' on https://{}:{}", path, domain, port);
+            }
+            locations.extend(matched);
+        }
+        redirects.push((
+            (domain.clone(), DEFAULT_PORT),
+            *port,
+            server.clone(),
+            locations,
+        ));
+    }
+
+    for (key, https_port, tls_server, locations) in redirects {
+        let server = server_map.entry(key.clone()).or_insert_with(|| Server {
+            port: None,
+            tls: None,
+            locations: vec![],
+            ..tls_server
+        });
+        if server.tls.is_some() {
+            warn!("port {} of '{}' is already used with TLS", key.1, key.0);
+            continue;
+        }
+        for location in locations {
+            if !server
+                .locations
+                .iter()
+                .any(|x| x.location == location.location)
+            {
+                server.locations.push(location);
+            }
+        }
+        if server.locations.iter().any(|x| x.location == "/") {
+            warn!(
+                "'/' of http://{} is already routed, no redirect added",
+                key.0
+            );
+        } else {
+            server.https_redirect = Some(https_port);
+        }
+    }
+}
+
+fn get_http(server_map: &HashMap<ServerKey, Server>) -> Http {
+    let locations = || server_map.values().flat_map(|x| x.locations.iter());
+    let mut cors_maps: Vec<_> = locations()
+        .filter_map(|x| x.cors.clone())
+        .filter(|x| x.map.is_some())
+        .collect();
+    cors_maps.sort_by(|a, b| a.map.cmp(&b.map));
+    cors_maps.dedup_by(|a, b| a.map == b.map);
+    let mut rate_limits: Vec<_> = locations().filter_map(|x| x.rate_limit.clone()).collect();
+    // copies of a location on the http redirect server share the zone
+    rate_limits.sort_by(|a, b| a.zone.cmp(&b.zone));
+    rate_limits.dedup_by(|a, b| a.zone == b.zone);
+    let mut conn_limits: Vec<_> = locations().filter_map(|x| x.conn_limit.clone()).collect();
+    conn_limits.sort_by(|a, b| a.zone.cmp(&b.zone));
+    conn_limits.dedup_by(|a, b| a.zone == b.zone);
+    Http {
+        connection_upgrade: locations().any(|x| x.websocket && x.domain.is_some() && !x.is_grpc),
+        cors_maps,
+        rate_limits,
+        conn_limits,
+    }
+}
+
 pub fn output(parsed_result: &ParsedResult) -> Result<(), CustomError> {
-    for (user, password) in parsed_result.basic_auth_map.iter() {
+    if parsed_result.self_signed_certificate {
+        write_self_signed_certificate(parsed_result)?;
+    }
+
+    for (user_file, users) in parsed_result.basic_auth_map.iter() {
+        let lines = users
+            .iter()
+            .map(|(user, password)| match password {
+                Password::Plain(password) => {
+                    create_htpasswd(user, password, parsed_result.htpasswd_hash)
+                }
+                Password::Hashed(hash) => Ok(format!("{}:{}", user, hash)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        write_to_file(Path::new(user_file), &lines.join("\n"))?;
+    }
+
+    if let Some(stream) = &parsed_result.stream {
         write_to_file(
-            &get_basic_auth_file_path(&parsed_result.target_dir, user, password),
-            &create_htpasswd(user, password),
+            &parsed_result.target_dir.join(STREAM_FILE_NAME),
+            &stream.render().map_err(|e| {
+                CustomError::new(format!("render failed {:?}, Error:{:?}", stream, e))
+            })?,
         )?;
     }
 
-    for (key, value) in parsed_result.server_map.iter() {
-        let filename = if key == "*" {
-            "default.conf".to_string()
-        } else {
-            format!("{}.conf", key.replace(".", "_"))
-        };
-
+    let mut server_map = parsed_result.server_map.clone();
+    add_https_redirects(&mut server_map);
+    let http = get_http(&server_map);
+    if !http.is_empty() {
         write_to_file(
-            &parsed_result.target_dir.join(filename),
+            &parsed_result.target_dir.join(HTTP_FILE_NAME),
+            &http.render().map_err(|e| {
+                CustomError::new(format!("render failed {:?}, Error:{:?}", http, e))
+            })?,
+        )?;
+    }
+    for (key, value) in server_map.iter() {
+        write_to_file(
+            &parsed_result.target_dir.join(get_server_file_name(key)),
             &value.render().map_err(|e| {
                 CustomError::new(format!("render failed {:?}, Error:{:?}", value, e))
             })?,
@@ -62,3 +280,137 @@ pub fn output(parsed_result: &ParsedResult) -> Result<(), CustomError> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{parse, Config};
+
+    #[test]
+    fn test_write_private_file() {
+        let path = std::env::temp_dir().join("ee-nginx-test-private.key");
+        std::fs::write(&path, "old").unwrap();
+        write_private_file(&path, "secret").unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "secret");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_get_server_file_name() {
+        for (key, expected) in [
+            (("*".to_string(), 80), "default.conf"),
+            (("*".to_string(), 8080), "default_8080.conf"),
+            (("foo.localhost".to_string(), 80), "foo_localhost.conf"),
+            (
+                ("foo.localhost".to_string(), 3333),
+                "foo_localhost_3333.conf",
+            ),
+        ] {
+            assert_eq!(get_server_file_name(&key), expected);
+        }
+    }
+
+    #[test]
+    fn test_add_https_redirects() {
+        let config = Config {
+            ssl_certificate: Some("/etc/ssl/{domain}.pem".to_string()),
+            ssl_certificate_key: Some("/etc/ssl/{domain}.key".to_string()),
+            ..Default::default()
+        };
+        let parsed_result = parse(
+            &PathBuf::from("/etc/nginx/conf.d"),
+            r#"
+            https://example.com/?redirect-http-exempt=/.well-known/ > http://app:8000/
+            https://example.com/.well-known/acme-challenge/ > /var/www/certbot/
+            https://*:8443/?redirect-http > /var/www/html/
+            http://*/health > http://app:8000/health
+            https://foo.localhost/ > /var/www/html/
+            "#,
+            &config,
+            "",
+            &HashMap::new(),
+        )
+        .expect("parse failed");
+        let mut server_map = parsed_result.server_map.clone();
+        add_https_redirects(&mut server_map);
+
+        assert_eq!(server_map.len(), parsed_result.server_map.len() + 1);
+        let server = &server_map[&("example.com".to_string(), 80)];
+        assert_eq!(server.tls, None);
+        assert_eq!(server.https_redirect, Some(443));
+        assert_eq!(
+            server
+                .locations
+                .iter()
+                .map(|x| x.location.as_str())
+                .collect::<Vec<_>>(),
+            vec!["/.well-known/", "/.well-known/acme-challenge/"]
+        );
+        // the `/` route narrowed to the exempt path
+        assert_eq!(
+            server.locations[0].domain.as_deref(),
+            Some("http://app:8000")
+        );
+        assert_eq!(server.locations[0].alias, "/.well-known/");
+
+        let server = &server_map[&("*".to_string(), 80)];
+        assert_eq!(server.https_redirect, Some(8443));
+        assert_eq!(
+            server
+                .locations
+                .iter()
+                .map(|x| x.location.as_str())
+                .collect::<Vec<_>>(),
+            vec!["/health"]
+        );
+
+        assert!(!server_map.contains_key(&("foo.localhost".to_string(), 80)));
+    }
+
+    #[test]
+    fn test_get_http() {
+        let config = Config::default();
+        for (input, connection_upgrade) in [
+            ("/ > /var/www/html/", false),
+            ("/ > http://app:8000/?no-ws", false),
+            ("/ > grpc://app:50051", false),
+            ("/ > /var/www/html/; /api > http://app:8000/", true),
+        ] {
+            let parsed_result = parse(
+                &PathBuf::from("/etc/nginx/conf.d"),
+                input,
+                &config,
+                "",
+                &HashMap::new(),
+            )
+            .expect("parse failed");
+            assert_eq!(
+                get_http(&parsed_result.server_map).connection_upgrade,
+                connection_upgrade,
+                "{}",
+                input
+            );
+        }
+
+        let parsed_result = parse(
+            &PathBuf::from("/etc/nginx/conf.d"),
+            "/a > /var/www/a/?cors=https://a.com; /b > /var/www/b/?cors=https://a.com; /c > /var/www/c/?cors=*",
+            &config,
+            "",
+            &HashMap::new(),
+        )
+        .expect("parse failed");
+        let http = get_http(&parsed_result.server_map);
+        assert_eq!(
+            http.cors_maps
+                .iter()
+                .map(|x| x.map.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["cors_origin_1"]
+        );
+    }
+}
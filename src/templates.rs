@@ -1,44 +1,372 @@
 use askama::Template;
 
-use crate::CacheType;
+use crate::{
+    utils::{escape_regex, force_append_trailing_slash, quote_string},
+    CacheType, HashAlgorithm, LoadBalancing, SecurityPreset,
+};
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
 pub struct Config {
     pub docker_mode: bool,
+    pub ssl_certificate: Option<String>,
+    pub ssl_certificate_key: Option<String>,
+    pub self_signed_certificate: bool,
+    pub htpasswd_hash: HashAlgorithm,
+}
+
+impl Config {
+    /// Default certificate paths for `domain`, `{domain}` in the paths is replaced by it.
+    pub fn ssl_certificate_for(&self, domain: &str) -> (Option<String>, Option<String>) {
+        let domain = if domain == "*" { "default" } else { domain };
+        let replace = |path: &Option<String>| path.as_ref().map(|p| p.replace("{domain}", domain));
+        (
+            replace(&self.ssl_certificate),
+            replace(&self.ssl_certificate_key),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Response {
+    Redirect {
+        code: u16,
+        url: String,
+        keep_path: bool,
+        keep_query: bool,
+    },
+    Text {
+        code: u16,
+        content_type: String,
+        body: String,
+    },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UpstreamServer {
+    /// host:port
+    pub address: String,
+    pub weight: Option<u32>,
+    pub max_fails: Option<u32>,
+    pub backup: bool,
+    /// re-resolve the host name at runtime, requires the zone/resolver of docker mode
+    pub resolve: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Upstream {
+    pub name: String,
+    pub load_balancing: LoadBalancing,
+    pub servers: Vec<UpstreamServer>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+    /// also add the header to error responses
+    pub always: bool,
+}
+
+/// Response headers of a security preset, all sent with `always`.
+fn security_headers(preset: SecurityPreset) -> Vec<Header> {
+    let basic = [
+        ("X-Content-Type-Options", "nosniff"),
+        ("X-Frame-Options", "SAMEORIGIN"),
+        ("Referrer-Policy", "strict-origin-when-cross-origin"),
+    ];
+    let strict = [
+        // ignored by browsers on plain http
+        (
+            "Strict-Transport-Security",
+            "max-age=63072000; includeSubDomains",
+        ),
+        ("X-Content-Type-Options", "nosniff"),
+        ("X-Frame-Options", "DENY"),
+        ("Referrer-Policy", "no-referrer"),
+        (
+            "Content-Security-Policy",
+            "default-src 'self'; object-src 'none'; base-uri 'self'; frame-ancestors 'none'",
+        ),
+        (
+            "Permissions-Policy",
+            "camera=(), microphone=(), geolocation=()",
+        ),
+        ("Cross-Origin-Opener-Policy", "same-origin"),
+    ];
+    let headers: &[_] = match preset {
+        SecurityPreset::Basic => &basic,
+        SecurityPreset::Strict => &strict,
+    };
+    headers
+        .iter()
+        .map(|(name, value)| Header {
+            name: name.to_string(),
+            value: value.to_string(),
+            always: true,
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Headers {
+    /// response headers, `add_header`
+    pub add: Vec<Header>,
+    /// request headers sent to the backend
+    pub proxy: Vec<Header>,
+    /// backend response headers not passed to the client
+    pub hide: Vec<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Cors {
+    /// `*` or `scheme://host[:port]` with an optional `*.` subdomain wildcard
+    pub origins: Vec<String>,
+    pub credentials: bool,
+    /// variable of the `map` on `$http_origin`, None when any origin is allowed
+    pub map: Option<String>,
+}
+
+impl Cors {
+    fn allow_origin(&self) -> String {
+        match &self.map {
+            Some(map) => format!("${}", map),
+            None => "\"*\"".to_string(),
+        }
+    }
+
+    fn varies(&self) -> bool {
+        self.map.is_some()
+    }
+
+    fn map_source(&self, origin: &str) -> String {
+        match origin.split_once("://*.") {
+            Some((scheme, host)) => format!(
+                "~^{}://[^.]+\\.{}$",
+                escape_regex(scheme),
+                escape_regex(host)
+            ),
+            None => origin.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RateLimit {
+    /// `limit_req_zone` name, set by `parse()` as every route has its own zone
+    pub zone: String,
+    /// `$binary_remote_addr` or a `$http_*` variable
+    pub key: String,
+    /// e.g. `10r/s`
+    pub rate: String,
+    pub burst: Option<u32>,
+    pub nodelay: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ConnLimit {
+    /// `limit_conn_zone` name, set by `parse()` like `RateLimit::zone`
+    pub zone: String,
+    /// concurrent connections per client IP
+    pub max: u32,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BasicAuth {
+    pub realm: String,
+    pub user_file: String,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AccessRule {
+    pub allow: bool,
+    /// CIDR, address or `all`
+    pub address: String,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FastCgi {
+    /// host:port or unix:/path/to.sock
+    pub pass: String,
+    pub root: String,
 }
 
 #[derive(Template, Debug, Clone, Eq, PartialEq)]
-#[template(path = "location.jinja")]
+#[template(path = "location.jinja", escape = "none")]
 pub struct Location<'a> {
     pub config: &'a Config,
     pub location: String,
     pub domain: Option<String>,
     pub alias: String,
     pub fallback: bool,
-    pub basic_auth: Option<String>,
+    pub basic_auth: Option<BasicAuth>,
     pub cache_type: CacheType,
+    pub max_age: Option<u32>,
     pub nameserver: String,
     pub show_index: bool,
     pub is_file: bool,
     pub enable_sse: bool,
+    /// forward `Upgrade` requests of proxied locations
+    pub websocket: bool,
+    pub headers: Headers,
+    pub cors: Option<Cors>,
+    /// preset of the route, or else of the server
+    pub security: Option<SecurityPreset>,
+    pub rate_limit: Option<RateLimit>,
+    pub conn_limit: Option<ConnLimit>,
+    /// bandwidth per connection, e.g. `1m`
+    pub limit_rate: Option<String>,
+    /// `allow`/`deny` in order, replacing those of the server
+    pub access: Vec<AccessRule>,
+    /// either an allowed address or basic auth is enough
+    pub satisfy_any: bool,
+    /// `domain` is a grpc:// or grpcs:// backend
+    pub is_grpc: bool,
+    pub response: Option<Response>,
+    pub upstream: Option<Upstream>,
+    pub fastcgi: Option<FastCgi>,
+}
+
+impl<'a> Location<'a> {
+    fn cache_max_age(&self) -> u32 {
+        self.max_age.unwrap_or(31536000)
+    }
+
+    /// Captures the path after the location, `/old` must not match a sibling like `/oldies`.
+    fn keep_path_regex(&self) -> String {
+        if self.location.ends_with('/') {
+            format!("^{}([^?]*)", escape_regex(&self.location))
+        } else {
+            format!("^{}(/[^?]*)?(?:\\?|$)", escape_regex(&self.location))
+        }
+    }
+
+    fn is_unix_socket(&self) -> bool {
+        self.domain
+            .as_ref()
+            .is_some_and(|domain| domain.contains("://unix:"))
+    }
+
+    fn fastcgi_index(&self) -> String {
+        format!("{}index.php", force_append_trailing_slash(&self.location))
+    }
+
+    fn quote(&self, s: &str) -> String {
+        quote_string(s)
+    }
+
+    /// Headers of the security preset followed by `add_header`, which overrides preset headers.
+    fn add_headers(&self) -> Vec<Header> {
+        let preset = self.security.map(security_headers).unwrap_or_default();
+        let is_overridden = |x: &Header| {
+            self.headers
+                .add
+                .iter()
+                .any(|y| y.name.eq_ignore_ascii_case(&x.name))
+        };
+        let overrides = self.headers.add.iter().map(|x| Header {
+            always: x.always || preset.iter().any(|y| y.name.eq_ignore_ascii_case(&x.name)),
+            ..x.clone()
+        });
+        let mut headers: Vec<_> = preset
+            .iter()
+            .filter(|x| !is_overridden(x))
+            .cloned()
+            .collect();
+        headers.extend(overrides);
+        headers
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Tls {
+    pub certificate: String,
+    pub certificate_key: String,
+    /// Paths exempted from the HTTP to HTTPS redirect, `None` when no redirect is requested
+    pub redirect_http: Option<Vec<String>>,
 }
 
 #[derive(Template, Debug, Clone, Eq, PartialEq)]
-#[template(path = "server.jinja")]
+#[template(path = "server.jinja", escape = "none")]
 pub struct Server<'a> {
     pub config: &'a Config,
     pub domain: Option<String>,
     pub port: Option<u16>,
+    pub tls: Option<Tls>,
+    /// HTTPS port to redirect requests not matched by `locations` to
+    pub https_redirect: Option<u16>,
+    /// preset set on a route, rendered in every location since `add_header` is not inherited by
+    /// a location with its own
+    pub security: Option<SecurityPreset>,
+    pub access: Vec<AccessRule>,
     pub locations: Vec<Location<'a>>,
 }
 
+impl<'a> Server<'a> {
+    fn http2(&self) -> bool {
+        self.locations.iter().any(|x| x.is_grpc)
+    }
+
+    fn https_redirect_url(&self) -> Option<String> {
+        self.https_redirect.map(|port| {
+            if port == 443 {
+                "https://$host$request_uri".to_string()
+            } else {
+                format!("https://$host:{}$request_uri", port)
+            }
+        })
+    }
+}
+
+/// http level settings shared by all servers
+#[derive(Template, Debug, Clone, Default, Eq, PartialEq)]
+#[template(path = "http.jinja", escape = "none")]
+pub struct Http {
+    pub connection_upgrade: bool,
+    /// cors settings with a `map`, one per variable
+    pub cors_maps: Vec<Cors>,
+    /// one `limit_req_zone` each
+    pub rate_limits: Vec<RateLimit>,
+    /// one `limit_conn_zone` each
+    pub conn_limits: Vec<ConnLimit>,
+}
+
+impl Http {
+    pub fn is_empty(&self) -> bool {
+        *self == Http::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StreamProtocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StreamServer {
+    pub protocol: StreamProtocol,
+    /// listen address, `None` for all addresses
+    pub address: Option<String>,
+    pub port: u16,
+    /// host:port
+    pub proxy_pass: String,
+    pub nameserver: String,
+}
+
+#[derive(Template, Debug, Clone, Eq, PartialEq)]
+#[template(path = "stream.jinja", escape = "none")]
+pub struct Stream<'a> {
+    pub config: &'a Config,
+    pub servers: Vec<StreamServer>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_location_0() {
-        let config = Config { docker_mode: false };
+        let config = Config::default();
         assert_eq!(
             Location {
                 config: &config,
@@ -48,10 +376,24 @@ mod tests {
                 fallback: false,
                 basic_auth: None,
                 cache_type: CacheType::None,
+                max_age: None,
                 nameserver: "".to_string(),
                 show_index: false,
                 is_file: false,
                 enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
             }
             .render()
             .expect("failed to render location"),
@@ -65,7 +407,7 @@ mod tests {
 
     #[test]
     fn test_location_1() {
-        let config = Config { docker_mode: false };
+        let config = Config::default();
         assert_eq!(
             Location {
                 config: &config,
@@ -75,10 +417,24 @@ mod tests {
                 fallback: false,
                 basic_auth: None,
                 cache_type: CacheType::MustRevalidate,
+                max_age: None,
                 nameserver: "".to_string(),
                 show_index: false,
                 is_file: false,
                 enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
             }
             .render()
             .expect("failed to render location"),
@@ -92,7 +448,7 @@ mod tests {
 
     #[test]
     fn test_location_2() {
-        let config = Config { docker_mode: false };
+        let config = Config::default();
         assert_eq!(
             Location {
                 config: &config,
@@ -102,10 +458,24 @@ mod tests {
                 fallback: true,
                 basic_auth: None,
                 cache_type: CacheType::None,
+                max_age: None,
                 nameserver: "".to_string(),
                 show_index: false,
                 is_file: false,
                 enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
             }
             .render()
             .expect("failed to render location"),
@@ -116,7 +486,7 @@ mod tests {
     proxy_redirect off;
     proxy_http_version 1.1;
     proxy_set_header Upgrade $http_upgrade;
-    proxy_set_header Connection "upgrade";
+    proxy_set_header Connection $connection_upgrade;
     try_files $uri $uri/ / =404;
   }"#
         );
@@ -124,7 +494,7 @@ mod tests {
 
     #[test]
     fn test_location_3() {
-        let config = Config { docker_mode: false };
+        let config = Config::default();
         assert_eq!(
             Location {
                 config: &config,
@@ -132,12 +502,29 @@ mod tests {
                 domain: None,
                 alias: "/var/www/html/".to_string(),
                 fallback: false,
-                basic_auth: Some("/etc/nginx/conf.d/htpasswd".to_string()),
+                basic_auth: Some(BasicAuth {
+                    realm: "Authorization required".to_string(),
+                    user_file: "/etc/nginx/conf.d/htpasswd".to_string(),
+                }),
                 cache_type: CacheType::None,
+                max_age: None,
                 nameserver: "".to_string(),
                 show_index: false,
                 is_file: false,
                 enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
             }
             .render()
             .expect("failed to render location"),
@@ -151,9 +538,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_location_satisfy_any() {
+        let config = Config::default();
+        assert_eq!(
+            Location {
+                config: &config,
+                location: "/admin".to_string(),
+                domain: None,
+                alias: "/var/www/admin/".to_string(),
+                fallback: false,
+                basic_auth: Some(BasicAuth {
+                    realm: "Authorization required".to_string(),
+                    user_file: "/etc/nginx/conf.d/htpasswd".to_string(),
+                }),
+                cache_type: CacheType::None,
+                max_age: None,
+                nameserver: "".to_string(),
+                show_index: false,
+                is_file: false,
+                enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![
+                    AccessRule {
+                        allow: true,
+                        address: "10.0.0.0/8".to_string(),
+                    },
+                    AccessRule {
+                        allow: false,
+                        address: "all".to_string(),
+                    },
+                ],
+                satisfy_any: true,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"  location /admin {
+    allow 10.0.0.0/8;
+    deny all;
+    alias /var/www/admin/;
+    index index.html index.htm;
+    add_header Cache-Control "no-store";
+    satisfy any;
+    auth_basic "Authorization required";
+    auth_basic_user_file /etc/nginx/conf.d/htpasswd;
+  }"#
+        );
+    }
+
     #[test]
     fn test_location_4() {
-        let config = Config { docker_mode: false };
+        let config = Config::default();
         assert_eq!(
             Location {
                 config: &config,
@@ -163,10 +608,24 @@ mod tests {
                 fallback: false,
                 basic_auth: None,
                 cache_type: CacheType::None,
+                max_age: None,
                 nameserver: "".to_string(),
                 show_index: true,
                 is_file: false,
                 enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
             }
             .render()
             .expect("failed to render location"),
@@ -181,9 +640,328 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_location_versioned() {
+        let config = Config::default();
+        assert_eq!(
+            Location {
+                config: &config,
+                location: "/static".to_string(),
+                domain: None,
+                alias: "/var/www/html/".to_string(),
+                fallback: false,
+                basic_auth: None,
+                cache_type: CacheType::Versioned,
+                max_age: Some(3600),
+                nameserver: "".to_string(),
+                show_index: false,
+                is_file: false,
+                enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"  location /static {
+    alias /var/www/html/;
+    index index.html index.htm;
+    add_header Cache-Control "public, max-age=3600";
+  }"#
+        );
+    }
+
+    #[test]
+    fn test_location_redirect() {
+        let config = Config::default();
+        assert_eq!(
+            Location {
+                config: &config,
+                location: "/old".to_string(),
+                domain: None,
+                alias: "".to_string(),
+                fallback: false,
+                basic_auth: None,
+                cache_type: CacheType::None,
+                max_age: None,
+                nameserver: "".to_string(),
+                show_index: false,
+                is_file: false,
+                enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: Some(Response::Redirect {
+                    code: 301,
+                    url: "https://new.example.com/".to_string(),
+                    keep_path: false,
+                    keep_query: false,
+                }),
+                upstream: None,
+                fastcgi: None,
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"  location /old {
+    return 301 https://new.example.com/;
+  }"#
+        );
+    }
+
+    #[test]
+    fn test_location_redirect_keep_path() {
+        let config = Config::default();
+        assert_eq!(
+            Location {
+                config: &config,
+                location: "/v1.0".to_string(),
+                domain: None,
+                alias: "".to_string(),
+                fallback: false,
+                basic_auth: None,
+                cache_type: CacheType::None,
+                max_age: None,
+                nameserver: "".to_string(),
+                show_index: false,
+                is_file: false,
+                enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: Some(Response::Redirect {
+                    code: 308,
+                    url: "https://new.example.com/v2".to_string(),
+                    keep_path: true,
+                    keep_query: true,
+                }),
+                upstream: None,
+                fastcgi: None,
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"  location /v1.0 {
+    if ($request_uri ~ "^/v1\.0(/[^?]*)?(?:\?|$)") {
+      return 308 https://new.example.com/v2$1$is_args$args;
+    }
+    return 308 https://new.example.com/v2$is_args$args;
+  }"#
+        );
+    }
+
+    #[test]
+    fn test_location_keep_path_regex() {
+        let config = Config::default();
+        let location = |path: &str| Location {
+            config: &config,
+            location: path.to_string(),
+            domain: None,
+            alias: "".to_string(),
+            fallback: false,
+            basic_auth: None,
+            cache_type: CacheType::None,
+            max_age: None,
+            nameserver: "".to_string(),
+            show_index: false,
+            is_file: false,
+            enable_sse: false,
+            websocket: true,
+            headers: Headers::default(),
+            cors: None,
+            security: None,
+            rate_limit: None,
+            conn_limit: None,
+            limit_rate: None,
+            access: vec![],
+            satisfy_any: false,
+            is_grpc: false,
+            response: None,
+            upstream: None,
+            fastcgi: None,
+        };
+        let capture = |path: &str, request_uri: &str| {
+            regex::Regex::new(&location(path).keep_path_regex())
+                .unwrap()
+                .captures(request_uri)
+                .map(|x| x.get(1).map_or("", |x| x.as_str()).to_string())
+        };
+        assert_eq!(capture("/old", "/old"), Some("".to_string()));
+        assert_eq!(capture("/old", "/old?a=1"), Some("".to_string()));
+        assert_eq!(capture("/old", "/old/a/b?c=1"), Some("/a/b".to_string()));
+        assert_eq!(capture("/old", "/oldies"), None);
+        assert_eq!(capture("/old", "/oldies/a"), None);
+        assert_eq!(capture("/a/", "/a/b/c?d"), Some("b/c".to_string()));
+    }
+
+    #[test]
+    fn test_location_text() {
+        let config = Config::default();
+        assert_eq!(
+            Location {
+                config: &config,
+                location: "/status".to_string(),
+                domain: None,
+                alias: "".to_string(),
+                fallback: false,
+                basic_auth: None,
+                cache_type: CacheType::None,
+                max_age: None,
+                nameserver: "".to_string(),
+                show_index: false,
+                is_file: false,
+                enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: Some(Response::Text {
+                    code: 503,
+                    content_type: "application/json".to_string(),
+                    body: r#"{"message": "<down> \o/"}"#.to_string(),
+                }),
+                upstream: None,
+                fastcgi: None,
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"  location /status {
+    default_type application/json;
+    return 503 "{\"message\": \"<down> \\o/\"}";
+  }"#
+        );
+    }
+
+    #[test]
+    fn test_location_fastcgi() {
+        let config = Config::default();
+        assert_eq!(
+            Location {
+                config: &config,
+                location: "/app".to_string(),
+                domain: None,
+                alias: "".to_string(),
+                fallback: false,
+                basic_auth: None,
+                cache_type: CacheType::None,
+                max_age: None,
+                nameserver: "".to_string(),
+                show_index: false,
+                is_file: false,
+                enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: Some(FastCgi {
+                    pass: "unix:/run/php/php-fpm.sock".to_string(),
+                    root: "/srv/app".to_string(),
+                }),
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"  location /app {
+    root /srv/app;
+    index index.php index.html;
+    try_files $uri $uri/ /app/index.php$is_args$args;
+    location ~ \.php$ {
+      try_files $uri =404;
+      fastcgi_pass unix:/run/php/php-fpm.sock;
+      fastcgi_index index.php;
+      include fastcgi_params;
+      fastcgi_param SCRIPT_FILENAME /srv/app$fastcgi_script_name;
+    }
+  }"#
+        );
+    }
+
+    #[test]
+    fn test_location_unix_socket() {
+        let config = Config {
+            docker_mode: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Location {
+                config: &config,
+                location: "/".to_string(),
+                domain: Some("http://unix:/run/app.sock:".to_string()),
+                alias: "/prefix/".to_string(),
+                fallback: false,
+                basic_auth: None,
+                cache_type: CacheType::None,
+                max_age: None,
+                nameserver: "127.0.0.11".to_string(),
+                show_index: false,
+                is_file: false,
+                enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"  location / {
+    proxy_pass http://unix:/run/app.sock:/prefix/;
+    proxy_set_header Host $host;
+    proxy_set_header X-Real-IP $remote_addr;
+    proxy_redirect off;
+    proxy_http_version 1.1;
+    proxy_set_header Upgrade $http_upgrade;
+    proxy_set_header Connection $connection_upgrade;
+  }"#
+        );
+    }
+
     #[test]
     fn test_location_sse() {
-        let config = Config { docker_mode: false };
+        let config = Config::default();
         assert_eq!(
             Location {
                 config: &config,
@@ -193,10 +971,24 @@ mod tests {
                 fallback: false,
                 basic_auth: None,
                 cache_type: CacheType::None,
+                max_age: None,
                 nameserver: "".to_string(),
                 show_index: false,
                 is_file: false,
                 enable_sse: true,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
             }
             .render()
             .expect("failed to render location"),
@@ -207,7 +999,7 @@ mod tests {
     proxy_redirect off;
     proxy_http_version 1.1;
     proxy_set_header Upgrade $http_upgrade;
-    proxy_set_header Connection "upgrade";
+    proxy_set_header Connection $connection_upgrade;
     proxy_buffering off;
     proxy_cache off;
     chunked_transfer_encoding off;
@@ -215,9 +1007,227 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_location_no_websocket() {
+        let config = Config::default();
+        assert_eq!(
+            Location {
+                config: &config,
+                location: "/api".to_string(),
+                domain: Some("http://backend:8000".to_string()),
+                alias: "/".to_string(),
+                fallback: false,
+                basic_auth: None,
+                cache_type: CacheType::None,
+                max_age: None,
+                nameserver: "".to_string(),
+                show_index: false,
+                is_file: false,
+                enable_sse: false,
+                websocket: false,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"  location /api {
+    proxy_pass http://backend:8000/;
+    proxy_set_header Host $host;
+    proxy_set_header X-Real-IP $remote_addr;
+    proxy_redirect off;
+    proxy_http_version 1.1;
+    proxy_set_header Connection "";
+  }"#
+        );
+    }
+
+    #[test]
+    fn test_location_headers() {
+        let config = Config::default();
+        let header = |name: &str, value: &str, always| Header {
+            name: name.to_string(),
+            value: value.to_string(),
+            always,
+        };
+        assert_eq!(
+            Location {
+                config: &config,
+                location: "/api".to_string(),
+                domain: Some("http://backend:8000".to_string()),
+                alias: "/".to_string(),
+                fallback: false,
+                basic_auth: None,
+                cache_type: CacheType::None,
+                max_age: None,
+                nameserver: "".to_string(),
+                show_index: false,
+                is_file: false,
+                enable_sse: false,
+                websocket: false,
+                headers: Headers {
+                    add: vec![
+                        header("X-Frame-Options", "DENY", false),
+                        header("X-Note", r#"say "hi""#, true),
+                    ],
+                    proxy: vec![header("X-Forwarded-Host", "$host", false)],
+                    hide: vec!["X-Powered-By".to_string()],
+                },
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"  location /api {
+    proxy_pass http://backend:8000/;
+    proxy_set_header Host $host;
+    proxy_set_header X-Real-IP $remote_addr;
+    proxy_redirect off;
+    proxy_http_version 1.1;
+    proxy_set_header Connection "";
+    proxy_set_header X-Forwarded-Host "$host";
+    proxy_hide_header X-Powered-By;
+    add_header X-Frame-Options "DENY";
+    add_header X-Note "say \"hi\"" always;
+  }"#
+        );
+    }
+
+    #[test]
+    fn test_location_security() {
+        let config = Config::default();
+        assert_eq!(
+            Location {
+                config: &config,
+                location: "/".to_string(),
+                domain: Some("http://app:8000".to_string()),
+                alias: "/".to_string(),
+                fallback: false,
+                basic_auth: None,
+                cache_type: CacheType::None,
+                max_age: None,
+                nameserver: "".to_string(),
+                show_index: false,
+                is_file: false,
+                enable_sse: false,
+                websocket: false,
+                headers: Headers {
+                    add: vec![Header {
+                        name: "content-security-policy".to_string(),
+                        value: "default-src 'self' cdn.example.com".to_string(),
+                        always: false,
+                    }],
+                    ..Default::default()
+                },
+                cors: None,
+                security: Some(SecurityPreset::Strict),
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"  location / {
+    proxy_pass http://app:8000/;
+    proxy_set_header Host $host;
+    proxy_set_header X-Real-IP $remote_addr;
+    proxy_redirect off;
+    proxy_http_version 1.1;
+    proxy_set_header Connection "";
+    add_header Strict-Transport-Security "max-age=63072000; includeSubDomains" always;
+    add_header X-Content-Type-Options "nosniff" always;
+    add_header X-Frame-Options "DENY" always;
+    add_header Referrer-Policy "no-referrer" always;
+    add_header Permissions-Policy "camera=(), microphone=(), geolocation=()" always;
+    add_header Cross-Origin-Opener-Policy "same-origin" always;
+    add_header content-security-policy "default-src 'self' cdn.example.com" always;
+  }"#
+        );
+    }
+
+    #[test]
+    fn test_location_cors() {
+        let config = Config::default();
+        assert_eq!(
+            Location {
+                config: &config,
+                location: "/assets/".to_string(),
+                domain: None,
+                alias: "/var/www/assets/".to_string(),
+                fallback: false,
+                basic_auth: None,
+                cache_type: CacheType::None,
+                max_age: None,
+                nameserver: "".to_string(),
+                show_index: false,
+                is_file: false,
+                enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: Some(Cors {
+                    origins: vec!["*".to_string()],
+                    credentials: false,
+                    map: None,
+                }),
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"  location /assets/ {
+    if ($request_method = OPTIONS) {
+      add_header Access-Control-Allow-Origin "*";
+      add_header Access-Control-Allow-Methods "GET, POST, PUT, PATCH, DELETE, OPTIONS";
+      add_header Access-Control-Allow-Headers $http_access_control_request_headers;
+      add_header Access-Control-Max-Age 86400;
+      return 204;
+    }
+    alias /var/www/assets/;
+    index index.html index.htm;
+    add_header Cache-Control "no-store";
+    add_header Access-Control-Allow-Origin "*" always;
+  }"#
+        );
+    }
+
     #[test]
     fn test_location_file_0() {
-        let config = Config { docker_mode: true };
+        let config = Config {
+            docker_mode: true,
+            ..Default::default()
+        };
         assert_eq!(
             Location {
                 config: &config,
@@ -227,10 +1237,24 @@ mod tests {
                 fallback: false,
                 basic_auth: None,
                 cache_type: CacheType::None,
+                max_age: None,
                 nameserver: "".to_string(),
                 show_index: false,
                 is_file: true,
                 enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
             }
             .render()
             .expect("failed to render location"),
@@ -244,7 +1268,10 @@ mod tests {
 
     #[test]
     fn test_location_file_2() {
-        let config = Config { docker_mode: true };
+        let config = Config {
+            docker_mode: true,
+            ..Default::default()
+        };
         assert_eq!(
             Location {
                 config: &config,
@@ -254,10 +1281,24 @@ mod tests {
                 fallback: true,
                 basic_auth: None,
                 cache_type: CacheType::None,
+                max_age: None,
                 nameserver: "127.0.0.11".to_string(),
                 show_index: false,
                 is_file: true,
                 enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: false,
+                response: None,
+                upstream: None,
+                fastcgi: None,
             }
             .render()
             .expect("failed to render location"),
@@ -271,21 +1312,24 @@ mod tests {
     proxy_redirect off;
     proxy_http_version 1.1;
     proxy_set_header Upgrade $http_upgrade;
-    proxy_set_header Connection "upgrade";
+    proxy_set_header Connection $connection_upgrade;
     try_files $uri $uri/ / =404;
   }"#
         );
     }
 
-
     #[test]
     fn test_server_0() {
-        let config = Config { docker_mode: false };
+        let config = Config::default();
         assert_eq!(
             Server {
                 config: &config,
                 domain: None,
                 port: Some(99),
+                tls: None,
+                https_redirect: None,
+                security: None,
+                access: vec![],
                 locations: vec![]
             }
             .render()
@@ -299,12 +1343,16 @@ mod tests {
 
     #[test]
     fn test_server_1() {
-        let config = Config { docker_mode: false };
+        let config = Config::default();
         assert_eq!(
             Server {
                 config: &config,
                 domain: Some("foo.localhost".to_string()),
                 port: None,
+                tls: None,
+                https_redirect: None,
+                security: None,
+                access: vec![],
                 locations: vec![]
             }
             .render()
@@ -313,6 +1361,465 @@ mod tests {
   listen 80;
   client_max_body_size 1000M;
   server_name foo.localhost;
+}"#
+        );
+    }
+
+    #[test]
+    fn test_server_security() {
+        let config = Config::default();
+        assert_eq!(
+            Server {
+                config: &config,
+                domain: None,
+                port: None,
+                tls: None,
+                https_redirect: None,
+                security: Some(SecurityPreset::Basic),
+                access: vec![],
+                locations: vec![Location {
+                    config: &config,
+                    location: "/".to_string(),
+                    domain: None,
+                    alias: "/var/www/html/".to_string(),
+                    fallback: false,
+                    basic_auth: None,
+                    cache_type: CacheType::None,
+                    max_age: None,
+                    nameserver: "".to_string(),
+                    show_index: false,
+                    is_file: false,
+                    enable_sse: false,
+                    websocket: true,
+                    headers: Headers::default(),
+                    cors: None,
+                    security: Some(SecurityPreset::Basic),
+                    rate_limit: None,
+                    conn_limit: None,
+                    limit_rate: None,
+                    access: vec![],
+                    satisfy_any: false,
+                    is_grpc: false,
+                    response: None,
+                    upstream: None,
+                    fastcgi: None,
+                }]
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"server {
+  listen 80;
+  client_max_body_size 1000M;
+  location / {
+    alias /var/www/html/;
+    index index.html index.htm;
+    add_header Cache-Control "no-store";
+    add_header X-Content-Type-Options "nosniff" always;
+    add_header X-Frame-Options "SAMEORIGIN" always;
+    add_header Referrer-Policy "strict-origin-when-cross-origin" always;
+  }
+}"#
+        );
+    }
+
+    #[test]
+    fn test_server_tls() {
+        let config = Config::default();
+        assert_eq!(
+            Server {
+                config: &config,
+                domain: Some("example.com".to_string()),
+                port: None,
+                tls: Some(Tls {
+                    certificate: "/etc/ssl/example.pem".to_string(),
+                    certificate_key: "/etc/ssl/example.key".to_string(),
+                    redirect_http: None,
+                }),
+                https_redirect: None,
+                security: None,
+                access: vec![],
+                locations: vec![]
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"server {
+  listen 443 ssl;
+  client_max_body_size 1000M;
+  server_name example.com;
+  ssl_certificate /etc/ssl/example.pem;
+  ssl_certificate_key /etc/ssl/example.key;
+  ssl_protocols TLSv1.2 TLSv1.3;
+  ssl_prefer_server_ciphers off;
+  ssl_session_cache shared:SSL:10m;
+  ssl_session_timeout 1d;
+  ssl_session_tickets off;
+}"#
+        );
+    }
+
+    #[test]
+    fn test_server_upstream() {
+        let config = Config {
+            docker_mode: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Server {
+                config: &config,
+                domain: None,
+                port: None,
+                tls: None,
+                https_redirect: None,
+                security: None,
+                access: vec![],
+                locations: vec![Location {
+                    config: &config,
+                    location: "/api".to_string(),
+                    domain: Some("http://upstream_default_80_api".to_string()),
+                    alias: "/".to_string(),
+                    fallback: false,
+                    basic_auth: None,
+                    cache_type: CacheType::None,
+                    max_age: None,
+                    nameserver: "127.0.0.11".to_string(),
+                    show_index: false,
+                    is_file: false,
+                    enable_sse: false,
+                    websocket: true,
+                    headers: Headers::default(),
+                    cors: None,
+                    security: None,
+                    rate_limit: None,
+                    conn_limit: None,
+                    limit_rate: None,
+                    access: vec![],
+                    satisfy_any: false,
+                    is_grpc: false,
+                    response: None,
+                    upstream: Some(Upstream {
+                        name: "upstream_default_80_api".to_string(),
+                        load_balancing: LoadBalancing::LeastConn,
+                        servers: vec![
+                            UpstreamServer {
+                                address: "app1:8000".to_string(),
+                                weight: Some(3),
+                                max_fails: None,
+                                backup: false,
+                                resolve: true,
+                            },
+                            UpstreamServer {
+                                address: "172.17.0.2:8000".to_string(),
+                                weight: None,
+                                max_fails: Some(2),
+                                backup: true,
+                                resolve: false,
+                            },
+                        ],
+                    }),
+                    fastcgi: None,
+                }]
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"upstream upstream_default_80_api {
+  least_conn;
+  zone upstream_default_80_api 64k;
+  resolver 127.0.0.11 valid=2s ipv6=off;
+  server app1:8000 weight=3 resolve;
+  server 172.17.0.2:8000 max_fails=2 backup;
+}
+
+server {
+  listen 80;
+  client_max_body_size 1000M;
+  location /api {
+    resolver 127.0.0.11 valid=2s ipv6=off;
+    set $target "http://upstream_default_80_api/";
+    rewrite /api(.*) /$1 break;
+    proxy_pass $target$1$is_args$args;
+    proxy_set_header Host $host;
+    proxy_set_header X-Real-IP $remote_addr;
+    proxy_redirect off;
+    proxy_http_version 1.1;
+    proxy_set_header Upgrade $http_upgrade;
+    proxy_set_header Connection $connection_upgrade;
+  }
+}"#
+        );
+    }
+
+    #[test]
+    fn test_http() {
+        assert_eq!(Http::default().render().expect("failed to render http"), "");
+        assert_eq!(
+            Http {
+                connection_upgrade: true,
+                cors_maps: vec![Cors {
+                    origins: vec![
+                        "https://app.example.com".to_string(),
+                        "http://*.example.com:8080".to_string(),
+                    ],
+                    credentials: true,
+                    map: Some("cors_origin_1".to_string()),
+                }],
+                rate_limits: vec![RateLimit {
+                    zone: "rate_limit_1".to_string(),
+                    key: "$binary_remote_addr".to_string(),
+                    rate: "10r/s".to_string(),
+                    burst: Some(20),
+                    nodelay: false,
+                }],
+                conn_limits: vec![ConnLimit {
+                    zone: "conn_limit_1".to_string(),
+                    max: 2,
+                }],
+            }
+            .render()
+            .expect("failed to render http"),
+            r#"map $http_upgrade $connection_upgrade {
+  default upgrade;
+  '' "";
+}
+map $http_origin $cors_origin_1 {
+  default "";
+  https://app.example.com $http_origin;
+  ~^http://[^.]+\.example\.com:8080$ $http_origin;
+}
+limit_req_zone $binary_remote_addr zone=rate_limit_1:10m rate=10r/s;
+limit_conn_zone $binary_remote_addr zone=conn_limit_1:10m;
+"#
+        );
+    }
+
+    #[test]
+    fn test_stream() {
+        let config = Config::default();
+        let servers = vec![
+            StreamServer {
+                protocol: StreamProtocol::Udp,
+                address: Some("127.0.0.1".to_string()),
+                port: 53,
+                proxy_pass: "dns:53".to_string(),
+                nameserver: "127.0.0.11".to_string(),
+            },
+            StreamServer {
+                protocol: StreamProtocol::Tcp,
+                address: None,
+                port: 5432,
+                proxy_pass: "db:5432".to_string(),
+                nameserver: "127.0.0.11".to_string(),
+            },
+        ];
+        assert_eq!(
+            Stream {
+                config: &config,
+                servers: servers.clone(),
+            }
+            .render()
+            .expect("failed to render stream"),
+            r#"stream {
+  server {
+    listen 127.0.0.1:53 udp;
+    proxy_pass dns:53;
+  }
+  server {
+    listen 5432;
+    proxy_pass db:5432;
+  }
+}"#
+        );
+
+        let config = Config {
+            docker_mode: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Stream {
+                config: &config,
+                servers: servers[1..].to_vec(),
+            }
+            .render()
+            .expect("failed to render stream"),
+            r#"stream {
+  server {
+    listen 5432;
+    resolver 127.0.0.11 valid=2s ipv6=off;
+    set $target db:5432;
+    proxy_pass $target;
+  }
+}"#
+        );
+    }
+
+    #[test]
+    fn test_server_grpc() {
+        let config = Config::default();
+        assert_eq!(
+            Server {
+                config: &config,
+                domain: None,
+                port: None,
+                tls: None,
+                https_redirect: None,
+                security: None,
+                access: vec![],
+                locations: vec![Location {
+                    config: &config,
+                    location: "/helloworld.Greeter/".to_string(),
+                    domain: Some("grpc://greeter:50051".to_string()),
+                    alias: "/".to_string(),
+                    fallback: false,
+                    basic_auth: None,
+                    cache_type: CacheType::None,
+                    max_age: None,
+                    nameserver: "127.0.0.11".to_string(),
+                    show_index: false,
+                    is_file: false,
+                    enable_sse: false,
+                    websocket: true,
+                    headers: Headers::default(),
+                    cors: None,
+                    security: None,
+                    rate_limit: None,
+                    conn_limit: None,
+                    limit_rate: None,
+                    access: vec![],
+                    satisfy_any: false,
+                    is_grpc: true,
+                    response: None,
+                    upstream: None,
+                    fastcgi: None,
+                }]
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"server {
+  listen 80;
+  http2 on;
+  client_max_body_size 1000M;
+  location /helloworld.Greeter/ {
+    grpc_pass grpc://greeter:50051;
+    grpc_set_header Host $host;
+    grpc_set_header X-Real-IP $remote_addr;
+  }
+}"#
+        );
+
+        let config = Config {
+            docker_mode: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Location {
+                config: &config,
+                location: "/helloworld.Greeter/".to_string(),
+                domain: Some("grpc://greeter:50051".to_string()),
+                alias: "/".to_string(),
+                fallback: false,
+                basic_auth: None,
+                cache_type: CacheType::None,
+                max_age: None,
+                nameserver: "127.0.0.11".to_string(),
+                show_index: false,
+                is_file: false,
+                enable_sse: false,
+                websocket: true,
+                headers: Headers::default(),
+                cors: None,
+                security: None,
+                rate_limit: None,
+                conn_limit: None,
+                limit_rate: None,
+                access: vec![],
+                satisfy_any: false,
+                is_grpc: true,
+                response: None,
+                upstream: None,
+                fastcgi: None,
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"  location /helloworld.Greeter/ {
+    resolver 127.0.0.11 valid=2s ipv6=off;
+    set $target "grpc://greeter:50051";
+    grpc_pass $target;
+    grpc_set_header Host $host;
+    grpc_set_header X-Real-IP $remote_addr;
+  }"#
+        );
+    }
+
+    #[test]
+    fn test_ssl_certificate_for() {
+        let config = Config {
+            ssl_certificate: Some("/etc/letsencrypt/live/{domain}/fullchain.pem".to_string()),
+            ssl_certificate_key: Some("/etc/letsencrypt/live/{domain}/privkey.pem".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.ssl_certificate_for("example.com"),
+            (
+                Some("/etc/letsencrypt/live/example.com/fullchain.pem".to_string()),
+                Some("/etc/letsencrypt/live/example.com/privkey.pem".to_string())
+            )
+        );
+        assert_eq!(Config::default().ssl_certificate_for("*"), (None, None));
+    }
+
+    #[test]
+    fn test_server_https_redirect() {
+        let config = Config::default();
+        assert_eq!(
+            Server {
+                config: &config,
+                domain: Some("example.com".to_string()),
+                port: None,
+                tls: None,
+                https_redirect: Some(8443),
+                security: None,
+                access: vec![],
+                locations: vec![Location {
+                    config: &config,
+                    location: "/.well-known/".to_string(),
+                    domain: None,
+                    alias: "/var/www/certbot/".to_string(),
+                    fallback: false,
+                    basic_auth: None,
+                    cache_type: CacheType::None,
+                    max_age: None,
+                    nameserver: "".to_string(),
+                    show_index: false,
+                    is_file: false,
+                    enable_sse: false,
+                    websocket: true,
+                    headers: Headers::default(),
+                    cors: None,
+                    security: None,
+                    rate_limit: None,
+                    conn_limit: None,
+                    limit_rate: None,
+                    access: vec![],
+                    satisfy_any: false,
+                    is_grpc: false,
+                    response: None,
+                    upstream: None,
+                    fastcgi: None,
+                }]
+            }
+            .render()
+            .expect("failed to render location"),
+            r#"server {
+  listen 80;
+  client_max_body_size 1000M;
+  server_name example.com;
+  location /.well-known/ {
+    alias /var/www/certbot/;
+    index index.html index.htm;
+    add_header Cache-Control "no-store";
+  }
+  location / {
+    return 301 https://$host:8443$request_uri;
+  }
 }"#
         );
     }
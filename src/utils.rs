@@ -1,7 +1,5 @@
 use std::path::{Path, PathBuf};
 
-use base64::{engine::general_purpose, Engine};
-use openssl::hash::{Hasher, MessageDigest};
 use url::Url;
 
 pub fn get_domain(domain: Option<&str>) -> Option<String> {
@@ -34,14 +32,118 @@ pub fn force_append_trailing_slash(path: &str) -> String {
     }
 }
 
-pub fn get_basic_auth_file_path(target_dir: &Path, username: &str, password: &str) -> PathBuf {
-    let digest = {
-        let mut hasher = Hasher::new(MessageDigest::sha1()).unwrap();
-        hasher.update(username.as_bytes()).unwrap();
-        hasher.update(password.as_bytes()).unwrap();
-        hasher.finish().unwrap()
-    };
-    let hashed = general_purpose::URL_SAFE_NO_PAD.encode(digest);
+/// Splits `s` on `sep`, trimming each piece and dropping empty ones.
+/// Returns each piece together with its byte offset in `s`.
+pub fn split_with_offsets(s: &str, sep: char) -> Vec<(usize, &str)> {
+    let mut result = vec![];
+    let mut start = 0;
+    for piece in s.split(sep) {
+        let trimmed = piece.trim_start();
+        let offset = start + piece.len() - trimmed.len();
+        let trimmed = trimmed.trim_end();
+        if !trimmed.is_empty() {
+            result.push((offset, trimmed));
+        }
+        start += piece.len() + sep.len_utf8();
+    }
+    result
+}
+
+pub fn escape_regex(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\.^$|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Quotes `s` as an nginx string, `$` can not be escaped and still expands variables.
+pub fn quote_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+pub fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+pub fn find_closest<'a>(word: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|c| (levenshtein_distance(word, c), *c))
+        .filter(|(d, c)| *d <= 3 && *d < c.len())
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
 
-    target_dir.join(hashed)
+pub fn get_basic_auth_file_path(target_dir: &Path, index: usize) -> PathBuf {
+    target_dir.join(format!("htpasswd_{}", index))
+}
+
+pub fn get_self_signed_certificate_paths(target_dir: &Path) -> (PathBuf, PathBuf) {
+    (
+        target_dir.join("self-signed.crt"),
+        target_dir.join("self-signed.key"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_with_offsets() {
+        assert_eq!(
+            split_with_offsets(" /a > /b ;; /c>/d", ';'),
+            vec![(1, "/a > /b"), (12, "/c>/d")]
+        );
+        assert_eq!(
+            split_with_offsets("/a > /b", '>'),
+            vec![(0, "/a"), (5, "/b")]
+        );
+        assert_eq!(split_with_offsets("   ", ';'), vec![]);
+    }
+
+    #[test]
+    fn test_escape_regex() {
+        assert_eq!(escape_regex("/old/"), "/old/");
+        assert_eq!(escape_regex("/v1.0/(a)"), "/v1\\.0/\\(a\\)");
+    }
+
+    #[test]
+    fn test_quote_string() {
+        assert_eq!(quote_string("ok"), r#""ok""#);
+        assert_eq!(quote_string(""), r#""""#);
+        assert_eq!(quote_string(r#"say "hi" \o/"#), r#""say \"hi\" \\o/""#);
+    }
+
+    #[test]
+    fn test_levenshtein_distance() {
+        assert_eq!(levenshtein_distance("", ""), 0);
+        assert_eq!(levenshtein_distance("index", "index"), 0);
+        assert_eq!(levenshtein_distance("versionned", "versioned"), 1);
+        assert_eq!(levenshtein_distance("kitten", "sitting"), 3);
+        assert_eq!(levenshtein_distance("", "sse"), 3);
+    }
+
+    #[test]
+    fn test_find_closest() {
+        let candidates = ["versioned", "must-revalidate", "index", "sse"];
+        assert_eq!(find_closest("versionned", &candidates), Some("versioned"));
+        assert_eq!(find_closest("idx", &candidates), Some("index"));
+        assert_eq!(find_closest("ss", &candidates), Some("sse"));
+        assert_eq!(find_closest("completely-different", &candidates), None);
+    }
 }